@@ -0,0 +1,33 @@
+// scans generated programs of doubling size. scanning is linear when the time
+// per byte stays flat as the input grows
+//
+// cargo bench --bench scanner
+
+use rlox::scanner::Scanner;
+use std::time::Instant;
+
+// a chunk of ordinary lox with a bit of everything the scanner handles, non-ASCII text included
+const CHUNK: &str = r#"class Counter < Base {
+  init(start) { this.count = start; }
+  step() { this.count = this.count + 1; return this.count >= 10 and !false; }
+}
+// tally café counts, ünïcödé and 日本語 comments included
+var counter = Counter(0);
+while (counter.step() != nil) print "tally: " + "naïve 👍";
+fun area(r) { return 3.14159 * r * r / 2 - 0.5; }
+"#;
+
+fn main() {
+    println!("{:>12} {:>12} {:>12}", "bytes", "ms", "ns/byte");
+
+    for doublings in 0..6 {
+        let source = CHUNK.repeat(4096 << doublings);
+
+        let start = Instant::now();
+        let tokens = Scanner::new(source.as_str()).scan_tokens().value;
+        let elapsed = start.elapsed();
+
+        assert!(tokens.len() > 1);
+        println!("{:>12} {:>12.1} {:>12.2}", source.len(), elapsed.as_secs_f64() * 1e3, elapsed.as_nanos() as f64 / source.len() as f64);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{class::LoxInstance, environment::Environment, interpreter::{Interpreter, RuntimeError, Unwind}, parser::FunctionDecl, token::{Token, TokenType}, value::Value};
+
+use std::{cell::RefCell, fmt, rc::Rc};
+
+pub trait LoxCallable: fmt::Debug {
+    fn arity(&self) -> usize;
+    // takes an Rc so that callables like classes can hand out references to themselves.
+    // paren is the call's closing parenthesis, for errors that belong to the call itself
+    fn call(self: Rc<Self>, interpreter: &mut Interpreter, paren: &Token, arguments: Vec<Value>) -> Result<Value, RuntimeError>;
+}
+
+pub struct LoxFunction {
+    declaration: Rc<FunctionDecl>,
+    closure: Rc<RefCell<Environment>>,
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(declaration: Rc<FunctionDecl>, closure: Rc<RefCell<Environment>>, is_initializer: bool) -> Self {
+        LoxFunction {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    // wraps the closure in a scope where "this" is the given instance, turning a method into a bound method
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction {
+        let mut environment = Environment::new(Some(Rc::clone(&self.closure)));
+        environment.define("this".to_owned(), Value::Instance(instance));
+
+        LoxFunction::new(Rc::clone(&self.declaration), Rc::new(RefCell::new(environment)), self.is_initializer)
+    }
+
+    fn this(&self) -> Result<Value, RuntimeError> {
+        let this = Token {
+            token_type: TokenType::This,
+            lexeme: "this".into(),
+            ..self.declaration.name.clone()
+        };
+        self.closure.borrow().get_at(0, this)
+    }
+}
+
+impl LoxCallable for LoxFunction {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(self: Rc<Self>, interpreter: &mut Interpreter, _paren: &Token, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        let mut environment = Environment::new(Some(Rc::clone(&self.closure)));
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(param.lexeme.to_string(), argument);
+        }
+
+        match interpreter.execute_block(&self.declaration.body, Rc::new(RefCell::new(environment))) {
+            // an initializer always hands back the instance, even on an early "return;"
+            Ok(()) | Err(Unwind::Return(_)) if self.is_initializer => self.this(),
+            Ok(()) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(e)) => Err(e),
+        }
+    }
+}
+
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme)
+    }
+}
+
+// what a native function is built from. arguments have already been checked
+// against its arity, and an error message is reported at the call
+pub type NativeFn = dyn Fn(&[Value]) -> Result<Value, String>;
+
+pub struct NativeFunction {
+    arity: usize,
+    function: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new<F>(arity: usize, function: F) -> Self
+    where
+        F: Fn(&[Value]) -> Result<Value, String> + 'static,
+    {
+        NativeFunction {
+            arity,
+            function: Box::new(function),
+        }
+    }
+}
+
+impl LoxCallable for NativeFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(self: Rc<Self>, _interpreter: &mut Interpreter, paren: &Token, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        (self.function)(&arguments).map_err(|message| RuntimeError(paren.clone(), message))
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn>")
+    }
+}
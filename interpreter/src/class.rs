@@ -0,0 +1,92 @@
+use crate::{callable::{LoxCallable, LoxFunction}, interpreter::{Interpreter, RuntimeError}, token::Token, value::Value};
+
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+pub struct LoxClass {
+    name: String,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<String, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    pub fn new(name: String, superclass: Option<Rc<LoxClass>>, methods: HashMap<String, Rc<LoxFunction>>) -> Self {
+        LoxClass {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    // walks up the inheritance chain, so methods on a subclass override those of its superclasses
+    pub fn find_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        match self.methods.get(name) {
+            Some(m) => Some(Rc::clone(m)),
+            None => self.superclass.as_ref().and_then(|s| s.find_method(name))
+        }
+    }
+}
+
+// calling a class constructs a new instance of it
+impl LoxCallable for LoxClass {
+    fn arity(&self) -> usize {
+        match self.find_method("init") {
+            Some(initializer) => initializer.arity(),
+            None => 0
+        }
+    }
+
+    fn call(self: Rc<Self>, interpreter: &mut Interpreter, paren: &Token, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        let instance = Rc::new(RefCell::new(LoxInstance::new(Rc::clone(&self))));
+
+        if let Some(initializer) = self.find_method("init") {
+            Rc::new(initializer.bind(Rc::clone(&instance))).call(interpreter, paren, arguments)?;
+        }
+
+        Ok(Value::Instance(instance))
+    }
+}
+
+impl fmt::Debug for LoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+pub struct LoxInstance {
+    class: Rc<LoxClass>,
+    fields: HashMap<String, Value>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> Self {
+        LoxInstance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    // fields shadow methods. methods are bound on every access, so they
+    // can be passed around and still remember their instance
+    pub fn get(instance: &Rc<RefCell<LoxInstance>>, name: &Token) -> Result<Value, RuntimeError> {
+        if let Some(v) = instance.borrow().fields.get(&*name.lexeme) {
+            return Ok(v.clone());
+        }
+
+        let method = instance.borrow().class.find_method(&name.lexeme);
+        match method {
+            Some(m) => Ok(Value::Callable(Rc::new(m.bind(Rc::clone(instance))))),
+            None => Err(RuntimeError(name.clone(), format!("Undefined property '{}'.", name.lexeme)))
+        }
+    }
+
+    pub fn set(&mut self, name: &Token, value: Value) {
+        self.fields.insert(name.lexeme.to_string(), value);
+    }
+}
+
+// fields aren't printed since an instance can end up holding itself
+impl fmt::Debug for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{interpreter::RuntimeError, parser::ParseError, resolver::ResolveError, token::{Span, Token, TokenType}};
+
+use std::{fmt, rc::Rc};
+
+// an error tied to a range of the source, rendered rustc-style:
+//
+// error: Undefined variable 'x'.
+//  --> 2:7
+//   |
+// 2 | print x;
+//   |       ^
+//
+// spans are offsets into the source the error came from, which a diagnostic
+// holds on to. that isn't necessarily the source being run when it's reported:
+// a runtime error can happen in a function defined by an earlier eval
+#[derive(Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+    pub label: Option<String>,
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    pub source: Rc<str>,
+}
+
+// the output of a front end pass along with every error it ran into. with
+// errors the output is still fine for tooling to look at, but not to run
+pub struct Diagnosed<T> {
+    pub value: T,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl<T> Diagnosed<T> {
+    pub fn new(value: T, diagnostics: Vec<Diagnostic>) -> Self {
+        Diagnosed {
+            value,
+            diagnostics,
+        }
+    }
+}
+
+// points at some other part of the source that helps explain the error
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(message: String, span: Span, source: Rc<str>) -> Self {
+        Diagnostic {
+            message,
+            span,
+            label: None,
+            secondary: Vec::new(),
+            notes: Vec::new(),
+            source,
+        }
+    }
+
+    pub fn with_label(mut self, label: String) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: String) -> Self {
+        self.secondary.push(Label { span, message });
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    // 1-based line the error starts on
+    pub fn line(&self) -> usize {
+        position(&self.source, self.span.start).0
+    }
+
+    // the one line format the reference implementations print compile errors in:
+    //
+    // [line 2] Error at 'x': Expect ';' after value.
+    //
+    // the label, when there is one, says where on the line the error is
+    pub fn render_short(&self) -> String {
+        match &self.label {
+            Some(label) => format!("[line {}] Error {}: {}\n", self.line(), label, self.message),
+            None => format!("[line {}] Error: {}\n", self.line(), self.message),
+        }
+    }
+
+    pub fn render(&self, color: bool) -> String {
+        let source = &*self.source;
+        let paint = |code: &str, text: &str| {
+            if color {
+                format!("\x1b[{}m{}\x1b[0m", code, text)
+            } else {
+                text.to_owned()
+            }
+        };
+
+        let (line, column) = position(source, self.span.start);
+
+        // snippets are shown in source order, with the primary one underlined by ^ and the rest by -
+        let mut snippets = vec![(self.span, '^', self.label.as_deref().unwrap_or(""), ERROR)];
+        for l in &self.secondary {
+            snippets.push((l.span, '-', l.message.as_str(), NOTE));
+        }
+        snippets.sort_by_key(|(span, _, _, _)| span.start);
+
+        let width = snippets.iter().map(|(span, _, _, _)| position(source, span.start).0.to_string().len()).max().unwrap();
+        let gutter = paint(NOTE, &format!("{} |", " ".repeat(width)));
+
+        let mut out = format!("{}{}\n", paint(ERROR, "error"), paint(BOLD, &format!(": {}", self.message)));
+        out += &format!("{}{} {}:{}\n", " ".repeat(width), paint(NOTE, "-->"), line, column);
+        out += &format!("{}\n", gutter);
+
+        let mut previous_line = 0;
+        for (span, marker, label, code) in snippets {
+            let (line, column) = position(source, span.start);
+
+            // spans running over several lines are only underlined up to the end of the first
+            let length = source.get(span.start..span.end.max(span.start)).unwrap_or("").lines().next().unwrap_or("").chars().count().max(1);
+            let underline = format!("{} {}", marker.to_string().repeat(length), label);
+
+            // several labels on one line share a single copy of it
+            if line != previous_line {
+                out += &format!("{} {}\n", paint(NOTE, &format!("{:>width$} |", line, width = width)), line_text(source, span.start));
+                previous_line = line;
+            }
+            out += &format!("{} {}{}\n", gutter, " ".repeat(column - 1), paint(code, underline.trim_end()));
+        }
+
+        for n in &self.notes {
+            out += &format!("{} {} {}\n", " ".repeat(width), paint(NOTE, "="), paint(BOLD, &format!("note: {}", n)));
+        }
+
+        out
+    }
+}
+
+const ERROR: &str = "1;31";
+const NOTE: &str = "1;34";
+const BOLD: &str = "1";
+
+// the nearest character boundary at or before offset, so slicing at it can't panic
+fn boundary(source: &str, offset: usize) -> usize {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+
+    offset
+}
+
+// 1-based line and column of a byte offset, with columns counted in characters
+fn position(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..boundary(source, offset)];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+
+    (line, before[line_start..].chars().count() + 1)
+}
+
+fn line_text(source: &str, offset: usize) -> &str {
+    let offset = boundary(source, offset);
+    let start = source[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let end = source[offset..].find('\n').map(|i| offset + i).unwrap_or(source.len());
+
+    source[start..end].trim_end_matches('\r')
+}
+
+fn location(token: &Token) -> String {
+    if token.token_type == TokenType::Eof {
+        "at end".to_owned()
+    } else {
+        format!("at '{}'", token.lexeme)
+    }
+}
+
+impl From<ParseError> for Diagnostic {
+    fn from(error: ParseError) -> Self {
+        let ParseError(token, message) = error;
+        Diagnostic::new(message, token.span, Rc::clone(token.lexeme.source())).with_label(location(&token))
+    }
+}
+
+impl From<ResolveError> for Diagnostic {
+    fn from(error: ResolveError) -> Self {
+        let ResolveError(token, message) = error;
+        Diagnostic::new(message, token.span, Rc::clone(token.lexeme.source())).with_label(location(&token))
+    }
+}
+
+impl From<RuntimeError> for Diagnostic {
+    fn from(error: RuntimeError) -> Self {
+        let RuntimeError(token, message) = error;
+        Diagnostic::new(message, token.span, Rc::clone(token.lexeme.source()))
+    }
+}
+
+// leaves out the source, which can be any length
+impl fmt::Debug for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Diagnostic")
+            .field("message", &self.message)
+            .field("span", &self.span)
+            .field("label", &self.label)
+            .field("secondary", &self.secondary)
+            .field("notes", &self.notes)
+            .finish()
+    }
+}
@@ -1,52 +1,96 @@
-use crate::{interpreter::RuntimeError, token::{Literal, Token}};
+use crate::{interpreter::RuntimeError, token::Token, value::Value};
 
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
+// scopes are shared: a closure keeps its defining scope alive for as long as
+// the function itself lives, even after the block that created it has exited
 pub struct Environment {
-    enclosing: Option<Box<Environment>>,
-    values: HashMap<String, Literal>
+    enclosing: Option<Rc<RefCell<Environment>>>,
+    values: HashMap<String, Value>
 }
 
 impl Environment {
-    pub fn new(enclosing: Option<Box<Environment>>) -> Self {
+    pub fn new(enclosing: Option<Rc<RefCell<Environment>>>) -> Self {
         Environment {
             enclosing,
             values: HashMap::new(),
         }
     }
-    
-    pub fn define(&mut self, name: String, value: Literal) {
+
+    pub fn define(&mut self, name: String, value: Value) {
         self.values.insert(name, value);
     }
 
-    pub fn get(&self, name: Token) -> Result<Literal, RuntimeError> {
-        if self.values.contains_key(&name.lexeme) {
-            Ok(self.values.get(&name.lexeme).unwrap().clone())
+    // looks a name up in this scope only, without any source token to blame for a miss
+    pub fn get_local(&self, name: &str) -> Option<Value> {
+        self.values.get(name).cloned()
+    }
+
+    pub fn get(&self, name: Token) -> Result<Value, RuntimeError> {
+        if self.values.contains_key(&*name.lexeme) {
+            Ok(self.values.get(&*name.lexeme).unwrap().clone())
         } else {
             match &self.enclosing {
                 Some(e) => {
-                    e.get(name)
+                    e.borrow().get(name)
                 },
                 None => {
-                    Err(RuntimeError(name.clone(), format!("Undefined variable {}", name.lexeme)))
+                    Err(RuntimeError(name.clone(), format!("Undefined variable '{}'.", name.lexeme)))
                 }
             }
         }
     }
 
-    pub fn assign(&mut self, name: Token, value: Literal) -> Result<(), RuntimeError> {
-        if self.values.contains_key(&name.lexeme) {
-            self.values.insert(name.lexeme, value);
+    pub fn assign(&mut self, name: Token, value: Value) -> Result<(), RuntimeError> {
+        if let Some(v) = self.values.get_mut(&*name.lexeme) {
+            *v = value;
             Ok(())
         } else {
             match &mut self.enclosing {
                 Some(e) => {
-                    e.assign(name, value)
+                    e.borrow_mut().assign(name, value)
                 },
                 None => {
-                    Err(RuntimeError(name.clone(), format!("Undefined variable {}", name.lexeme)))
+                    Err(RuntimeError(name.clone(), format!("Undefined variable '{}'.", name.lexeme)))
                 }
             }
         }
     }
-}
\ No newline at end of file
+
+    // looks a name up in the scope exactly distance hops out, as computed by the resolver
+    pub fn get_at(&self, distance: usize, name: Token) -> Result<Value, RuntimeError> {
+        if distance == 0 {
+            match self.values.get(&*name.lexeme) {
+                Some(v) => Ok(v.clone()),
+                None => Err(RuntimeError(name.clone(), format!("Undefined variable '{}'.", name.lexeme)))
+            }
+        } else {
+            self.ancestor(distance).borrow().get_at(0, name)
+        }
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: Token, value: Value) -> Result<(), RuntimeError> {
+        if distance == 0 {
+            match self.values.get_mut(&*name.lexeme) {
+                Some(v) => {
+                    *v = value;
+                    Ok(())
+                },
+                None => Err(RuntimeError(name.clone(), format!("Undefined variable '{}'.", name.lexeme)))
+            }
+        } else {
+            self.ancestor(distance).borrow_mut().assign_at(0, name, value)
+        }
+    }
+
+    fn ancestor(&self, distance: usize) -> Rc<RefCell<Environment>> {
+        let mut environment = Rc::clone(self.enclosing.as_ref().unwrap());
+
+        for _ in 1..distance {
+            let enclosing = Rc::clone(environment.borrow().enclosing.as_ref().unwrap());
+            environment = enclosing;
+        }
+
+        environment
+    }
+}
@@ -1,248 +1,446 @@
-use crate::{Lox, environment::Environment, parser::{Expr, Stmt}, token::{Literal, Token, TokenType}};
-use std::error;
+use crate::{callable::{LoxCallable, LoxFunction, NativeFunction}, class::{LoxClass, LoxInstance}, environment::Environment, parser::{Expr, ExprKind, Stmt, StmtKind}, stack, token::{Literal, Token, TokenType}, value::Value};
+use std::{cell::{Cell, RefCell}, collections::HashMap, error, io::{self, Write}, rc::Rc, time::{SystemTime, UNIX_EPOCH}};
+
+// every lox call recurses through evaluate, so runaway recursion has to be
+// stopped well before it overflows the native stack and aborts the process
+const MAX_CALL_DEPTH: usize = 1000;
 
 pub struct Interpreter {
-    environment: Box<Environment>
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    output: Box<dyn Write>,
+    call_depth: usize,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
-        Interpreter {
-            environment: Box::new(Environment::new(None))
-        }
+        let globals = Rc::new(RefCell::new(Environment::new(None)));
+
+        let mut interpreter = Interpreter {
+            globals: Rc::clone(&globals),
+            environment: globals,
+            output: Box::new(io::stdout()),
+            call_depth: 0,
+        };
+
+        interpreter.define_native("clock", 0, |_| {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            Ok(Value::Number(now.as_secs_f64()))
+        });
+
+        interpreter
+    }
+
+    // exposes a rust function to lox code as a global. calls with the wrong
+    // number of arguments never reach it, and an Err it returns becomes a
+    // runtime error at the call
+    pub fn define_native<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(&[Value]) -> Result<Value, String> + 'static,
+    {
+        self.define_global(name, Value::Callable(Rc::new(NativeFunction::new(arity, function))));
+    }
+
+    // where print statements write to, stdout by default
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = output;
+    }
+
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        self.globals.borrow_mut().define(name.to_owned(), value);
     }
 
-    pub fn interpret(&mut self, stmts: Vec<Box<Stmt>>) {
-        for s in stmts {
-            self.interpret_stmt(s);
+    pub fn global(&self, name: &str) -> Option<Value> {
+        self.globals.borrow().get_local(name)
+    }
+
+    // stops at the first runtime error. the result is the value of the last
+    // statement if it's an expression statement, and nil otherwise
+    pub fn interpret(&mut self, stmts: Vec<Stmt>) -> Result<Value, RuntimeError> {
+        let mut last = Value::Nil;
+
+        for s in &stmts {
+            last = Value::Nil;
+            let result = match &s.kind {
+                StmtKind::Expr(e) => self.evaluate(e).map(|v| last = v).map_err(Unwind::from),
+                _ => self.interpret_stmt(s),
+            };
+
+            match result {
+                Ok(()) => {},
+                Err(Unwind::Error(e)) => return Err(e),
+                Err(Unwind::Return(_)) => return Ok(Value::Nil),
+            }
         }
+
+        Ok(last)
     }
 
-    pub fn interpret_stmt(&mut self, stmt: Box<Stmt>) -> Option<Literal> { // function has to be method due to weird lazy static error
-        match *stmt {
-            Stmt::Print(e) => {
-                let f = self.evaluate(e);
+    pub fn interpret_stmt(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
+        stack::guarded(|| self.interpret_stmt_kind(stmt))
+    }
 
-                match f {
-                    Ok(l) => { 
-                        println!("{:?}", l);
-                        None
-                    },
-                    Err(e) => {
-                        Lox::runtime_error(e);
-                        None
-                    }
-                }
+    fn interpret_stmt_kind(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
+        match &stmt.kind {
+            StmtKind::Print(e) => {
+                let l = self.evaluate(e)?;
+                // output that can't be written, like to a closed pipe, is dropped rather than stopping the program
+                let _ = writeln!(self.output, "{}", l);
             },
-            Stmt::Var(name, initializer) => {
-                let mut value = Literal::Nil;
+            StmtKind::Var(name, initializer) => {
+                let mut value = Value::Nil;
 
-                match *initializer {
-                    Some(e) => {
-                        let f = self.evaluate(Box::new(e));
-
-                        match f {
-                            Ok(l) => { 
-                                value = l;
-                            },
-                            Err(e) => {
-                                Lox::runtime_error(e);
-                            }
-                        };
-                    },
-                    None => {}
+                if let Some(e) = &**initializer {
+                    value = self.evaluate(e)?;
                 }
 
-                self.environment.define(name.lexeme, value);
-                None
+                self.environment.borrow_mut().define(name.lexeme.to_string(), value);
+            },
+            StmtKind::Block(stmts) => {
+                let scope = Environment::new(Some(Rc::clone(&self.environment)));
+                self.execute_block(stmts, Rc::new(RefCell::new(scope)))?;
             },
-            Stmt::Block(stmts) => {
-                for s in stmts {
-                    self.interpret_stmt(Box::new(s));
+            StmtKind::If(condition, then_branch, else_branch) => {
+                if Interpreter::is_truthy(self.evaluate(condition)?) {
+                    self.interpret_stmt(then_branch)?;
+                } else if let Some(e) = else_branch {
+                    self.interpret_stmt(e)?;
                 }
-                
-                None
             },
-            Stmt::If(condition, then_branch, else_branch) => {
-                if Interpreter::is_truthy(self.evaluate(condition).unwrap()) {
-                    self.interpret_stmt(then_branch);
-                } else {
-                    self.interpret_stmt(else_branch.unwrap());
+            StmtKind::While(condition, body) => {
+                while Interpreter::is_truthy(self.evaluate(condition)?) {
+                    self.interpret_stmt(body)?;
                 }
-
-                None
             },
-            Stmt::Expr(e) => {
-                let f = self.evaluate(e);
-        
-                match f {
-                    Ok(l) => Some(l),
-                    Err(e) => {
-                        Lox::runtime_error(e);
-                        None
+            StmtKind::Function(declaration) => {
+                let function = LoxFunction::new(Rc::clone(declaration), Rc::clone(&self.environment), false);
+                self.environment.borrow_mut().define(declaration.name.lexeme.to_string(), Value::Callable(Rc::new(function)));
+            },
+            StmtKind::Class(name, superclass_expr, declarations) => {
+                let mut superclass = None;
+                if let Some(e) = superclass_expr {
+                    match self.evaluate(e)? {
+                        Value::Class(c) => superclass = Some(c),
+                        _ => {
+                            let token = match &e.kind { ExprKind::Var(t, _) => t, _ => name };
+                            return Err(Unwind::Error(RuntimeError(token.clone(), "Superclass must be a class.".to_owned())));
+                        }
                     }
                 }
+
+                self.environment.borrow_mut().define(name.lexeme.to_string(), Value::Nil);
+
+                // methods of a subclass close over an extra scope binding "super"
+                let enclosing = Rc::clone(&self.environment);
+                if let Some(s) = &superclass {
+                    let mut environment = Environment::new(Some(Rc::clone(&enclosing)));
+                    environment.define("super".to_owned(), Value::Class(Rc::clone(s)));
+                    self.environment = Rc::new(RefCell::new(environment));
+                }
+
+                let mut methods = HashMap::new();
+                for d in declarations {
+                    let is_initializer = d.name.lexeme == "init";
+                    let method = LoxFunction::new(Rc::clone(d), Rc::clone(&self.environment), is_initializer);
+                    methods.insert(d.name.lexeme.to_string(), Rc::new(method));
+                }
+
+                self.environment = enclosing;
+
+                let class = LoxClass::new(name.lexeme.to_string(), superclass, methods);
+                self.environment.borrow_mut().assign(name.clone(), Value::Class(Rc::new(class)))?;
+            },
+            StmtKind::Return(_, value) => {
+                let mut v = Value::Nil;
+
+                if let Some(e) = value {
+                    v = self.evaluate(e)?;
+                }
+
+                // unwinds through every enclosing statement up to the function call
+                return Err(Unwind::Return(v));
+            },
+            StmtKind::Expr(e) => {
+                self.evaluate(e)?;
             },
         }
+
+        Ok(())
+    }
+
+    // runs stmts in the given scope, restoring the current one afterwards
+    // even if a statement errors or returns
+    pub fn execute_block(&mut self, stmts: &[Stmt], environment: Rc<RefCell<Environment>>) -> Result<(), Unwind> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = stmts.iter().try_for_each(|s| self.interpret_stmt(s));
+        self.environment = previous;
+
+        result
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
+        match &expr.kind {
+            // these don't recurse, and are common enough that checking the stack for them shows
+            ExprKind::Literal(_) | ExprKind::Var(_, _) | ExprKind::This(_, _) => self.evaluate_kind(expr),
+            _ => stack::guarded(|| self.evaluate_kind(expr)),
+        }
     }
 
-    fn evaluate(&mut self, expr: Box<Expr>) -> Result<Literal, RuntimeError> {
-        match *expr {
-            Expr::Unary(t, e) => {
+    fn evaluate_kind(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
+        match &expr.kind {
+            ExprKind::Unary(t, e) => {
                 self.evaluate_unary(t, e)
             },
-            Expr::Binary(l, t, r) => {
+            ExprKind::Binary(l, t, r) => {
                 self.evaluate_binary(l, t, r)
             },
-            Expr::Grouping(g) => {
+            ExprKind::Grouping(g) => {
                 self.evaluate_grouping(g)
             },
-            Expr::Literal(l) => {
+            ExprKind::Literal(l) => {
                 self.evaluate_literal(l)
             },
-            Expr::Var(t) => {
-                self.environment.get(t)
+            ExprKind::Var(t, depth) | ExprKind::This(t, depth) => {
+                self.look_up_variable(t, depth)
             },
-            Expr::Assignment(t, expr) => {
+            ExprKind::Assignment(t, expr, depth) => {
                 let value = self.evaluate(expr)?;
-                self.environment.assign(t, value.clone())?;
+
+                match depth.get() {
+                    Some(d) => self.environment.borrow_mut().assign_at(d, t.clone(), value.clone())?,
+                    None => self.globals.borrow_mut().assign(t.clone(), value.clone())?
+                }
+
                 Ok(value)
             },
-            Expr::Logical(left, operator, right) => {
-                let l = self.evaluate();
+            ExprKind::Logical(left, operator, right) => {
+                let l = self.evaluate(left)?;
 
+                // short-circuit: hand back the operand that decided the result
+                // rather than coercing it to a bool
                 match operator.token_type {
-                    TokenType::Or => {
-                        if Interpreter::is_truthy(self.evaluate(left)) {
-                            return left;
-                        }
-                    },
-                    TokenType::And => {
+                    TokenType::Or if Interpreter::is_truthy(l.clone()) => Ok(l),
+                    TokenType::And if !Interpreter::is_truthy(l.clone()) => Ok(l),
+                    _ => self.evaluate(right)
+                }
+            },
+            ExprKind::Call(callee, paren, arguments) => {
+                let callee = self.evaluate(callee)?;
 
-                    }
+                let mut args = Vec::new();
+                for a in arguments {
+                    args.push(self.evaluate(a)?);
+                }
+
+                let function: Rc<dyn LoxCallable> = match callee {
+                    Value::Callable(f) => f,
+                    Value::Class(c) => c,
+                    _ => return Err(RuntimeError(paren.clone(), "Can only call functions and classes.".to_owned()))
+                };
+
+                if args.len() != function.arity() {
+                    return Err(RuntimeError(paren.clone(), format!("Expected {} arguments but got {}.", function.arity(), args.len())));
+                }
+
+                if self.call_depth == MAX_CALL_DEPTH {
+                    return Err(RuntimeError(paren.clone(), "Stack overflow.".to_owned()));
+                }
+
+                self.call_depth += 1;
+                let result = function.call(self, paren, args);
+                self.call_depth -= 1;
+
+                result
+            },
+            ExprKind::Super(keyword, method, depth) => {
+                self.evaluate_super(keyword, method, depth)
+            },
+            ExprKind::Get(object, name) => {
+                match self.evaluate(object)? {
+                    Value::Instance(instance) => LoxInstance::get(&instance, name),
+                    _ => Err(RuntimeError(name.clone(), "Only instances have properties.".to_owned()))
+                }
+            },
+            ExprKind::Set(object, name, value) => {
+                match self.evaluate(object)? {
+                    Value::Instance(instance) => {
+                        let value = self.evaluate(value)?;
+                        instance.borrow_mut().set(name, value.clone());
+                        Ok(value)
+                    },
+                    _ => Err(RuntimeError(name.clone(), "Only instances have fields.".to_owned()))
                 }
             }
         }
     }
 
-    fn evaluate_unary(&mut self, t: Token, r: Box<Expr>) -> Result<Literal, RuntimeError> {
+    fn evaluate_super(&self, keyword: &Token, method: &Token, depth: &Cell<Option<usize>>) -> Result<Value, RuntimeError> {
+        // the resolver always finds "super" in a local scope, with "this" bound one scope further in
+        let distance = depth.get().unwrap();
+        let superclass = self.environment.borrow().get_at(distance, keyword.clone())?;
+        let this = Token {
+            token_type: TokenType::This,
+            lexeme: "this".into(),
+            ..keyword.clone()
+        };
+        let object = self.environment.borrow().get_at(distance - 1, this)?;
+
+        match (superclass, object) {
+            (Value::Class(superclass), Value::Instance(instance)) => {
+                match superclass.find_method(&method.lexeme) {
+                    Some(m) => Ok(Value::Callable(Rc::new(m.bind(instance)))),
+                    None => Err(RuntimeError(method.clone(), format!("Undefined property '{}'.", method.lexeme)))
+                }
+            },
+            _ => Err(RuntimeError(keyword.clone(), "Can't use 'super' outside of a class.".to_owned()))
+        }
+    }
+
+    fn look_up_variable(&self, name: &Token, depth: &Cell<Option<usize>>) -> Result<Value, RuntimeError> {
+        match depth.get() {
+            Some(d) => self.environment.borrow().get_at(d, name.clone()),
+            None => self.globals.borrow().get(name.clone())
+        }
+    }
+
+    fn evaluate_unary(&mut self, t: &Token, r: &Expr) -> Result<Value, RuntimeError> {
         let r = self.evaluate(r)?;
     
         match t.token_type {
             TokenType::Bang => {
-                return Ok(Literal::Bool(Interpreter::is_truthy(r)));
+                Ok(Value::Bool(Interpreter::is_truthy(r)))
             },
             TokenType::Minus => {
                 match r {
-                    Literal::Number(r) => {
-                        return Ok(Literal::Number(r * -1 as f64));
+                    Value::Number(r) => {
+                        Ok(Value::Number(-r))
                     },
-                    _ => Err(RuntimeError(t, "Operand must be a number".to_owned()))
+                    _ => Err(RuntimeError(t.clone(), "Operand must be a number.".to_owned()))
                 }
             },
-            _ => Ok(Literal::Nil) // unreachable
+            _ => Ok(Value::Nil) // unreachable
         }
     }
 
-    fn evaluate_binary(&mut self, l: Box<Expr>, t: Token, r: Box<Expr>) -> Result<Literal, RuntimeError> {
+    fn evaluate_binary(&mut self, l: &Expr, t: &Token, r: &Expr) -> Result<Value, RuntimeError> {
         let l = self.evaluate(l)?;
         let r = self.evaluate(r)?;
 
         match t.token_type {
             TokenType::Plus => {
                 match (l, r) {
-                    (Literal::Number(l), Literal::Number(r)) => {
-                        return Ok(Literal::Number(l + r));
+                    (Value::Number(l), Value::Number(r)) => {
+                        Ok(Value::Number(l + r))
                     },
-                    (Literal::String(l), Literal::String(r)) => {
-                        return Ok(Literal::String(l + &r));
+                    (Value::String(l), Value::String(r)) => {
+                        Ok(Value::String(format!("{}{}", l, r).into()))
                     },
-                    _ => Err(RuntimeError(t, "Operands must be numbers".to_owned()))
+                    _ => Err(RuntimeError(t.clone(), "Operands must be two numbers or two strings.".to_owned()))
                 }
             },
             TokenType::Minus => {
                 match (l, r) {
-                    (Literal::Number(l), Literal::Number(r)) => return Ok(Literal::Number(l-r)),
-                    _ => Err(RuntimeError(t, "Operands must be numbers".to_owned()))
+                    (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l-r)),
+                    _ => Err(RuntimeError(t.clone(), "Operands must be numbers.".to_owned()))
                 }
             },
             TokenType::Star =>  {
                 match (l, r) {
-                    (Literal::Number(l), Literal::Number(r)) => return Ok(Literal::Number(l*r)),
-                    _ => Err(RuntimeError(t, "Operands must be numbers".to_owned()))
+                    (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l*r)),
+                    _ => Err(RuntimeError(t.clone(), "Operands must be numbers.".to_owned()))
                 }
             },
             TokenType::Slash => {
                 match (l, r) {
-                    (Literal::Number(l), Literal::Number(r)) => return Ok(Literal::Number(l/r)),
-                    _ => Err(RuntimeError(t, "Operands must be numbers".to_owned()))
+                    (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l/r)),
+                    _ => Err(RuntimeError(t.clone(), "Operands must be numbers.".to_owned()))
                 }
             },
             TokenType::Greater => {
                 match (l, r) {
-                    (Literal::Number(l), Literal::Number(r)) => return Ok(Literal::Bool(l > r)),
-                    _ => Err(RuntimeError(t, "Operands must be numbers".to_owned()))
+                    (Value::Number(l), Value::Number(r)) => Ok(Value::Bool(l > r)),
+                    _ => Err(RuntimeError(t.clone(), "Operands must be numbers.".to_owned()))
                 }
             },
             TokenType::GreaterEqual => {
                 match (l, r) {
-                    (Literal::Number(l), Literal::Number(r)) => return Ok(Literal::Bool(l >= r)),
-                    _ => Err(RuntimeError(t, "Operands must be numbers".to_owned()))
+                    (Value::Number(l), Value::Number(r)) => Ok(Value::Bool(l >= r)),
+                    _ => Err(RuntimeError(t.clone(), "Operands must be numbers.".to_owned()))
                 }
             },
             TokenType::Less => {
                 match (l, r) {
-                    (Literal::Number(l), Literal::Number(r)) => return Ok(Literal::Bool(l < r)),
-                    _ => Err(RuntimeError(t, "Operands must be numbers".to_owned()))
+                    (Value::Number(l), Value::Number(r)) => Ok(Value::Bool(l < r)),
+                    _ => Err(RuntimeError(t.clone(), "Operands must be numbers.".to_owned()))
                 }
             },
             TokenType::LessEqual => {
                 match (l, r) {
-                    (Literal::Number(l), Literal::Number(r)) => return Ok(Literal::Bool(l <= r)),
-                    _ => Err(RuntimeError(t, "Operands must be numbers".to_owned()))
+                    (Value::Number(l), Value::Number(r)) => Ok(Value::Bool(l <= r)),
+                    _ => Err(RuntimeError(t.clone(), "Operands must be numbers.".to_owned()))
                 }
             },
-            TokenType::EqualEqual => return Ok(Literal::Bool(Interpreter::is_equal(l, r))),
-            TokenType::BangEqual => return Ok(Literal::Bool(!Interpreter::is_equal(l, r))),
+            TokenType::EqualEqual => Ok(Value::Bool(Interpreter::is_equal(l, r))),
+            TokenType::BangEqual => Ok(Value::Bool(!Interpreter::is_equal(l, r))),
             _ => {
-                Ok(Literal::Nil) // unreachable
+                Ok(Value::Nil) // unreachable
             }
         }
     }
 
-    fn evaluate_grouping(&mut self, g: Box<Expr>) -> Result<Literal, RuntimeError> {
+    fn evaluate_grouping(&mut self, g: &Expr) -> Result<Value, RuntimeError> {
         self.evaluate(g)
     }
 
-    fn evaluate_literal(&self, l: Literal) -> Result<Literal, RuntimeError> {
-        Ok(l)
+    fn evaluate_literal(&self, l: &Literal) -> Result<Value, RuntimeError> {
+        Ok(Value::from(l))
     }
 
     // ======== HELPERS ========
-    fn is_truthy(l: Literal) -> bool {
+    fn is_truthy(l: Value) -> bool {
         match l {
-            Literal::Nil => false,
-            Literal::Bool(b) => b,
+            Value::Nil => false,
+            Value::Bool(b) => b,
             _ => true
         }
     }
 
-    fn is_equal(l: Literal, r: Literal) -> bool {
+    fn is_equal(l: Value, r: Value) -> bool {
         match (l, r) {
-            (Literal::Nil, Literal::Nil) => return true,
-            (Literal::Nil, _) => return false,
-            (l, r) => return l == r,
+            (Value::Nil, Value::Nil) => true,
+            (Value::Nil, _) => false,
+            (l, r) => l == r,
         }
     }
 }
 
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
 #[derive(Debug)]
 pub struct RuntimeError(pub Token, pub String);
 
 impl std::fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}, {}", self.0.to_string(), self.1)
+        write!(f, "{}, {}", self.0, self.1)
     }
 }
 
-impl error::Error for RuntimeError {}
\ No newline at end of file
+impl error::Error for RuntimeError {}
+
+// anything that stops a statement from running to completion: either a
+// runtime error or a return statement unwinding to its function call
+#[derive(Debug)]
+pub enum Unwind {
+    Error(RuntimeError),
+    Return(Value),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(e: RuntimeError) -> Self {
+        Unwind::Error(e)
+    }
+}
\ No newline at end of file
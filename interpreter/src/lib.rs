@@ -0,0 +1,20 @@
+// rlox as a library, for running Lox inside another Rust program. Session is
+// the place to start, the modules below it are the interpreter's own pieces
+// for anything that needs to look at a program rather than just run it
+
+pub mod token;
+pub mod scanner;
+pub mod parser;
+pub mod resolver;
+pub mod interpreter;
+pub mod value;
+pub mod callable;
+pub mod class;
+pub mod diagnostic;
+mod environment;
+mod session;
+mod stack;
+
+pub use diagnostic::Diagnostic;
+pub use session::{Error, ErrorFormat, Session};
+pub use value::Value;
@@ -1,67 +1,63 @@
-mod token;
-mod scanner;
-mod parser;
-mod interpreter;
-mod environment;
-
-use std::{env, sync::Mutex};
+use std::env;
 use std::process;
-use std::io;
+use std::io::{self, IsTerminal};
 use std::fs;
-use std::sync::atomic::{AtomicBool, Ordering};
-
-use interpreter::{Interpreter, RuntimeError};
-use token::{Token, TokenType};
-use scanner::Scanner;
-use parser::{ParseError, Parser};
 
-use lazy_static::lazy_static;
+use rlox::{Error, ErrorFormat, Session};
 
 
 fn main() {
+    let mut session = Session::new();
+    session.set_diagnostic_output(io::stderr(), io::stderr().is_terminal());
+
     let mut lox = Lox{
-        had_error: false
+        session,
+        had_error: false,
+        had_runtime_error: false,
     };
     lox.main();
 }
 
-static HAD_ERROR: AtomicBool = AtomicBool::new(false);
-static HAD_RUNTIME_ERROR: AtomicBool = AtomicBool::new(false);
-
-lazy_static! {
-    static ref INTERPRETER: Mutex<Interpreter> = Mutex::new(Interpreter::new());
-}
-
-
 struct Lox {
-    had_error: bool
+    session: Session,
+    had_error: bool,
+    had_runtime_error: bool,
 }
 
 impl Lox {
     fn main(&mut self) {
-        let _args_: Vec<String> = env::args().collect();
+        let mut args: Vec<String> = env::args().collect();
 
-        self.run_prompt();
+        // errors one per line like the reference implementations print them, which the test suite checks against
+        if let Some(i) = args.iter().position(|a| a == "--error-format=short") {
+            args.remove(i);
+            self.session.set_error_format(ErrorFormat::Short);
+        }
 
-        // if args.len() > 1 {
-        //     println!("Usage: rlox [script]");
-        //     process::exit(64);
-        // } else if args.len() == 1 {
-        //     self.run_file(&args[0]).unwrap();
-        // } else {
-        //     self.run_prompt();
-        // }
+        // args[0] is the binary itself
+        match args.len() {
+            1 => self.run_prompt(),
+            2 => {
+                if let Err(e) = self.run_file(&args[1]) {
+                    eprintln!("Could not read file {}: {}", args[1], e);
+                    process::exit(74);
+                }
+            },
+            _ => {
+                eprintln!("Usage: rlox [--error-format=short] [script]");
+                process::exit(64);
+            }
+        }
     }
 
-    fn _run_file(&self, path: &str) -> io::Result<()> {
-        let bytes = fs::read(path).unwrap();
-        let string = std::str::from_utf8(&bytes).unwrap().to_owned();
+    fn run_file(&mut self, path: &str) -> io::Result<()> {
+        let string = fs::read_to_string(path)?;
         self.run(string);
 
-        if HAD_ERROR.load(Ordering::Relaxed) == true {
+        if self.had_error {
             process::exit(64);
         }
-        if HAD_RUNTIME_ERROR.load(Ordering::Relaxed) == true {
+        if self.had_runtime_error {
             process::exit(70);
         }
 
@@ -71,10 +67,10 @@ impl Lox {
     fn run_prompt(&mut self) {
         loop {
             println!("> ");
-            
+
             let mut line = String::new();
             io::stdin().read_line(&mut line).unwrap();
-            if line.len() == 0 {
+            if line.is_empty() {
                 break;
             }
             self.run(line);
@@ -83,43 +79,13 @@ impl Lox {
         }
     }
 
-    fn run(&self, source: String) {
-        let mut scanner = Scanner::new(source);
-        let tokens: Vec<Token> = scanner.scan_tokens();
-        let mut parser = Parser::new(tokens);
-        let stmts = parser.parse();
-
-        if self.had_error {
-            return;
-        }
-
-        let mut i = INTERPRETER.lock().unwrap();
-        i.interpret(stmts);
-    }
-
-    fn error(line: u32, message: String) {
-        Lox::report(line, "".to_owned(), message);
-    }
-
-    fn parse_error(error: ParseError) {
-        let ParseError(token, message) = error;
-        
-        if token.token_type == TokenType::Eof {
-            Lox::report(token.line, "at end".to_owned(), message)
-        } else {
-            Lox::report(token.line, format!("at, {}", token.lexeme), message)
+    fn run(&mut self, source: String) {
+        // the session has already written the error out
+        if let Err(e) = self.session.eval(&source) {
+            match e {
+                Error::Runtime(_) => self.had_runtime_error = true,
+                _ => self.had_error = true,
+            }
         }
     }
-
-    fn runtime_error(error: RuntimeError) {
-        let RuntimeError(token, message) = error;
-
-        println!("{} \n[line {}]", message, token.line);
-        HAD_RUNTIME_ERROR.store(true, Ordering::Relaxed);
-    }
-
-    fn report(line: u32, where_: String, message: String) {
-        println!("[line {}] Error {}: {}", line, where_, message);
-        HAD_ERROR.store(true, Ordering::Relaxed);
-    }
 }
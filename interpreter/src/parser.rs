@@ -1,5 +1,5 @@
-use crate::{Lox, token::{Token, TokenType, Literal}};
-use std::error;
+use crate::{diagnostic::{Diagnosed, Diagnostic}, stack, token::{Token, TokenType, Literal, Span}, value::Value};
+use std::{cell::Cell, error, rc::Rc};
 
 // ======== SYNTAX GRAMMAR ========
 // program        → declaration* EOF ;
@@ -71,34 +71,111 @@ use std::error;
 
 // ================================
 
+// every node records the range of source it was parsed from
 #[derive(Debug)]
-pub enum Expr {
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Expr {
+            kind,
+            span,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ExprKind {
     Unary(Token, Box<Expr>),
     Binary(Box<Expr>, Token, Box<Expr>),
     Grouping(Box<Expr>),
     Literal(Literal),
-    Var(Token),
-    Assignment(Token, Box<Expr>),
-    Logical(Box<Expr>, Token, Box<Expr>)
+    // the Cell holds the number of scopes between the variable and its
+    // declaration, filled in by the resolver. None means it's a global
+    Var(Token, Cell<Option<usize>>),
+    Assignment(Token, Box<Expr>, Cell<Option<usize>>),
+    Logical(Box<Expr>, Token, Box<Expr>),
+    Call(Box<Expr>, Token, Vec<Expr>),
+    Get(Box<Expr>, Token),
+    Set(Box<Expr>, Token, Box<Expr>),
+    This(Token, Cell<Option<usize>>),
+    Super(Token, Token, Cell<Option<usize>>)
+}
+
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Stmt {
+            kind,
+            span,
+        }
+    }
 }
 
-pub enum Stmt {
+pub enum StmtKind {
     Expr(Box<Expr>),
     Print(Box<Expr>),
     Var(Token, Box<Option<Expr>>),
     Block(Vec<Stmt>),
-    If(Box<Expr>, Box<Stmt>, Option<Box<Stmt>>)
+    If(Box<Expr>, Box<Stmt>, Option<Box<Stmt>>),
+    While(Box<Expr>, Box<Stmt>),
+    Function(Rc<FunctionDecl>), // shared with every runtime function created from it
+    Return(Token, Option<Box<Expr>>),
+    Class(Token, Option<Box<Expr>>, Vec<Rc<FunctionDecl>>) // the superclass is always an ExprKind::Var
 }
 
+pub struct FunctionDecl {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+}
+
+// prints the tree lisp-style, with every operator wrapped in parentheses
+// along with its operands, so 1 + 2 * 3 comes out as (+ 1 (* 2 3))
 impl std::fmt::Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "")
-    }    
+        match &self.kind {
+            ExprKind::Unary(operator, e) => write!(f, "({} {})", operator.lexeme, e),
+            ExprKind::Binary(l, operator, r) | ExprKind::Logical(l, operator, r) => write!(f, "({} {} {})", operator.lexeme, l, r),
+            ExprKind::Grouping(e) => write!(f, "(group {})", e),
+            ExprKind::Literal(Literal::String(s)) => write!(f, "\"{}\"", s),
+            ExprKind::Literal(l) => write!(f, "{}", Value::from(l)),
+            ExprKind::Var(name, _) => write!(f, "{}", name.lexeme),
+            ExprKind::Assignment(name, value, _) => write!(f, "(= {} {})", name.lexeme, value),
+            ExprKind::Call(callee, _, arguments) => {
+                write!(f, "(call {}", callee)?;
+                for a in arguments {
+                    write!(f, " {}", a)?;
+                }
+                write!(f, ")")
+            },
+            ExprKind::Get(object, name) => write!(f, "(. {} {})", object, name.lexeme),
+            ExprKind::Set(object, name, value) => write!(f, "(= (. {} {}) {})", object, name.lexeme, value),
+            ExprKind::This(_, _) => write!(f, "this"),
+            ExprKind::Super(_, method, _) => write!(f, "(super {})", method.lexeme),
+        }
+    }
 }
 
+// keeps the door open for a bytecode implementation that stores the argument count in a single byte
+const MAX_ARGUMENTS: usize = 255;
+
+// nested expressions and statements are parsed, resolved and interpreted
+// recursively, so deep enough nesting would overflow the native stack
+const MAX_NESTING: usize = 255;
+
 pub struct Parser {
     tokens: Vec<Token>,
-    current: usize
+    current: usize,
+    diagnostics: Vec<Diagnostic>,
+    depth: usize,
 }
 
 impl Parser {
@@ -106,50 +183,105 @@ impl Parser {
         Parser {
             tokens,
             current: 0,
+            diagnostics: Vec::new(),
+            depth: 0,
         }
     }
 
-    pub fn parse(&mut self) -> Vec<Box<Stmt>> {
+    pub fn parse(&mut self) -> Diagnosed<Vec<Stmt>> {
         let mut statements = Vec::new();
 
         while !self.at_end() {
-            match self.declaration() {
-                Some(d) => statements.push(Box::new(d)),
-                None => {}
+            if let Some(d) = self.declaration() {
+                statements.push(d)
             }
         }
 
-        statements
+        Diagnosed::new(statements, std::mem::take(&mut self.diagnostics))
     }
 
     fn declaration(&mut self) -> Option<Stmt> {
-        if self.match_(&vec![TokenType::Var]) {
-            match self.var_declaration() {
-                Ok(s) => {
-                    Some(s)
-                },
-                Err(e) => {
-                    self.synchronize();
-                    Lox::parse_error(e);
-                    None
-                }
-            }
+        let declaration = if self.match_(&vec![TokenType::Class]) {
+            self.class_declaration()
+        } else if self.match_(&vec![TokenType::Fun]) {
+            let start = self.previous().span;
+            self.function("function").map(|f| Stmt::new(StmtKind::Function(f), self.span_from(start)))
+        } else if self.match_(&vec![TokenType::Var]) {
+            self.var_declaration()
         } else {
-            match self.statement() {
-                Ok(s) => {
-                    Some(s)
-                },
-                Err(e) => {
-                    self.synchronize();
-                    Lox::parse_error(e);
-                    None
+            self.statement()
+        };
+
+        match declaration {
+            Ok(s) => {
+                Some(s)
+            },
+            Err(e) => {
+                self.synchronize();
+                self.diagnostics.push(e.into());
+                None
+            }
+        }
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.previous().span;
+        let name = self.consume(TokenType::Identifier, "Expect class name.".to_owned())?;
+
+        let mut superclass = None;
+        if self.match_(&vec![TokenType::Less]) {
+            self.consume(TokenType::Identifier, "Expect superclass name.".to_owned())?;
+            let superclass_name = self.previous();
+            let span = superclass_name.span;
+            superclass = Some(Box::new(Expr::new(ExprKind::Var(superclass_name, Cell::new(None)), span)));
+        }
+
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.".to_owned())?;
+
+        let mut methods = Vec::new();
+        while !self.check(&TokenType::RightBrace) && !self.at_end() {
+            methods.push(self.function("method")?);
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.".to_owned())?;
+        Ok(Stmt::new(StmtKind::Class(name, superclass, methods), self.span_from(start)))
+    }
+
+    // kind is used in error messages since methods reuse this rule
+    fn function(&mut self, kind: &str) -> Result<Rc<FunctionDecl>, ParseError> {
+        let name = self.consume(TokenType::Identifier, format!("Expect {} name.", kind))?;
+        self.consume(TokenType::LeftParen, format!("Expect '(' after {} name.", kind))?;
+
+        let mut parameters = Vec::new();
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if parameters.len() >= MAX_ARGUMENTS {
+                    // reported, but the parser is still in a sane state so there's no need to synchronize
+                    self.diagnostics.push(Diagnostic::from(ParseError(self.peek().clone(), format!("Can't have more than {} parameters.", MAX_ARGUMENTS))));
+                }
+
+                parameters.push(self.consume(TokenType::Identifier, "Expect parameter name.".to_owned())?);
+
+                if !self.match_(&vec![TokenType::Comma]) {
+                    break;
                 }
             }
         }
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.".to_owned())?;
+
+        self.consume(TokenType::LeftBrace, format!("Expect '{{' before {} body.", kind))?;
+        let body = self.nested(Self::block)?;
+
+        Ok(Rc::new(FunctionDecl {
+            name,
+            params: parameters,
+            body,
+        }))
     }
 
     fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
-        let name = self.consume(TokenType::Identifier, "Expect variable name".to_owned())?;
+        let start = self.previous().span;
+        let name = self.consume(TokenType::Identifier, "Expect variable name.".to_owned())?;
 
         let mut initializer = None;
 
@@ -157,31 +289,40 @@ impl Parser {
             initializer = Some(self.expression()?);
         }
         
-        self.consume(TokenType::SemiColon, "Expect ';' after variable declaration".to_owned())?;
+        self.consume(TokenType::SemiColon, "Expect ';' after variable declaration.".to_owned())?;
 
-        Ok(Stmt::Var(name, Box::new(initializer)))
+        Ok(Stmt::new(StmtKind::Var(name, Box::new(initializer)), self.span_from(start)))
     }
 
     fn statement(&mut self) -> Result<Stmt, ParseError> {
         if self.match_(&vec![TokenType::Print]) {
             self.print_statement()
         } else if self.match_(&vec![TokenType::LeftBrace]) {
-            self.block_statement()
+            let start = self.previous().span;
+            let statements = self.nested(Self::block)?;
+            Ok(Stmt::new(StmtKind::Block(statements), self.span_from(start)))
         } else if self.match_(&vec![TokenType::If]) {
             self.if_statement()
+        } else if self.match_(&vec![TokenType::While]) {
+            self.while_statement()
+        } else if self.match_(&vec![TokenType::For]) {
+            self.for_statement()
+        } else if self.match_(&vec![TokenType::Return]) {
+            self.return_statement()
         } else {
             self.expression_statement()
         }
     }
 
     fn print_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.previous().span;
         let value = self.expression();
 
         match value {
             Ok(e) => {
                 let semicolon_exists = self.consume(TokenType::SemiColon, "Expect ';' after value.".to_owned());
                 match semicolon_exists {
-                    Ok(_) => Ok(Stmt::Print(Box::new(e))),
+                    Ok(_) => Ok(Stmt::new(StmtKind::Print(Box::new(e)), self.span_from(start))),
                     Err(e) => Err(e),
                 }
             },
@@ -189,7 +330,7 @@ impl Parser {
         }
     }
 
-    fn block_statement(&mut self) -> Result<Stmt, ParseError> {
+    fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
         let mut statements = Vec::new();
 
         while !self.check(&TokenType::RightBrace) && !self.at_end() {
@@ -198,22 +339,94 @@ impl Parser {
             }
         }
 
-        self.consume(TokenType::RightBrace, "Expect '}' after block".to_owned())?;
-        Ok(Stmt::Block(statements))
+        self.consume(TokenType::RightBrace, "Expect '}' after block.".to_owned())?;
+        Ok(statements)
     }
 
     fn if_statement(&mut self) -> Result<Stmt, ParseError> {
-        self.consume(TokenType::LeftBrace, "Expect '(' after if".to_owned())?;
+        let start = self.previous().span;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.".to_owned())?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.".to_owned())?;
+
+        // an else is claimed by the closest if, since the innermost if statement gets to look for it first
+        let then_branch = self.nested(Self::statement)?;
+        let else_branch = if self.match_(&vec![TokenType::Else]) {
+            Some(Box::new(self.nested(Self::statement)?))
+        } else {
+            None
+        };
+
+        Ok(Stmt::new(StmtKind::If(Box::new(condition), Box::new(then_branch), else_branch), self.span_from(start)))
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.previous().span;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.".to_owned())?;
         let condition = self.expression()?;
-        self.consume(TokenType::RightBrace, "Expect ')' after if condition".to_owned())?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.".to_owned())?;
+        let body = self.nested(Self::statement)?;
+
+        Ok(Stmt::new(StmtKind::While(Box::new(condition), Box::new(body)), self.span_from(start)))
+    }
 
-        let then_branch = self.statement()?;
-        if self.match_(&vec![TokenType::Else]) {
-            let else_branch = self.statement()?;
-            Ok(Stmt::If(Box::new(condition), Box::new(then_branch), Some(Box::new(else_branch))))
+    // there is no for node in the syntax tree. a for loop is desugared into
+    // its initializer followed by a while loop whose body runs the increment
+    // after the original body
+    fn for_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.previous().span;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.".to_owned())?;
+
+        let initializer = if self.match_(&vec![TokenType::SemiColon]) {
+            None
+        } else if self.match_(&vec![TokenType::Var]) {
+            Some(self.var_declaration()?)
         } else {
-            Ok(Stmt::If(Box::new(condition), Box::new(then_branch), None))
+            Some(self.expression_statement()?)
+        };
+
+        let mut condition = None;
+        if !self.check(&TokenType::SemiColon) {
+            condition = Some(self.expression()?);
         }
+        self.consume(TokenType::SemiColon, "Expect ';' after loop condition.".to_owned())?;
+
+        let mut increment = None;
+        if !self.check(&TokenType::RightParen) {
+            increment = Some(self.expression()?);
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.".to_owned())?;
+
+        let mut body = self.nested(Self::statement)?;
+        // the desugared nodes have no source of their own, so they all take the span of the whole loop
+        let span = self.span_from(start);
+
+        if let Some(i) = increment {
+            let increment_span = i.span;
+            body = Stmt::new(StmtKind::Block(vec![body, Stmt::new(StmtKind::Expr(Box::new(i)), increment_span)]), span);
+        }
+
+        let condition = condition.unwrap_or_else(|| Expr::new(ExprKind::Literal(Literal::Bool(true)), span));
+        body = Stmt::new(StmtKind::While(Box::new(condition), Box::new(body)), span);
+
+        if let Some(i) = initializer {
+            body = Stmt::new(StmtKind::Block(vec![i, body]), span);
+        }
+
+        Ok(body)
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous();
+
+        let mut value = None;
+        if !self.check(&TokenType::SemiColon) {
+            value = Some(Box::new(self.expression()?));
+        }
+
+        self.consume(TokenType::SemiColon, "Expect ';' after return value.".to_owned())?;
+        let span = self.span_from(keyword.span);
+        Ok(Stmt::new(StmtKind::Return(keyword, value), span))
     }
 
     fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
@@ -223,7 +436,10 @@ impl Parser {
             Ok(e) => {
                 let semicolon_exists = self.consume(TokenType::SemiColon, "Expect ';' after expression.".to_owned());
                 match semicolon_exists {
-                    Ok(_) => Ok(Stmt::Expr(Box::new(e))),
+                    Ok(_) => {
+                        let span = self.span_from(e.span);
+                        Ok(Stmt::new(StmtKind::Expr(Box::new(e)), span))
+                    },
                     Err(e) => Err(e)
                 }
             },
@@ -255,7 +471,7 @@ impl Parser {
 
     // ======== OPERATORS ========
     fn expression(&mut self) -> Result<Expr, ParseError> {
-        self.assignment()
+        self.nested(Self::assignment)
     }
 
     fn assignment(&mut self) -> Result<Expr, ParseError> {
@@ -267,14 +483,24 @@ impl Parser {
             // instead of looping like the other operators, we recurse
             // since assignment is right-associative. this means parse the
             // right hand side and wrap it all up in an assignment expression node
-            let value = self.assignment()?;
+            let value = self.nested(Self::assignment)?;
 
-            match value {
-                Expr::Assignment(t, e) => {
-                    Ok(Expr::Assignment(t, e))
+            let span = expr.span.to(value.span);
+            match expr.kind {
+                ExprKind::Var(name, _) => {
+                    Ok(Expr::new(ExprKind::Assignment(name, Box::new(value), Cell::new(None)), span))
+                },
+                ExprKind::Get(object, name) => {
+                    Ok(Expr::new(ExprKind::Set(object, name, Box::new(value)), span))
                 },
-                _ => {
-                    Err(ParseError(equals, "Invalid assignment target.".to_owned()))
+                // the parser isn't confused by a bad target, so there's no need to synchronize
+                kind => {
+                    let error = Diagnostic::from(ParseError(equals, "Invalid assignment target.".to_owned()));
+                    self.diagnostics.push(error
+                        .with_secondary(expr.span, "can't assign to this".to_owned())
+                        .with_note("only variables and fields can be assigned to".to_owned()));
+
+                    Ok(Expr::new(kind, expr.span))
                 }
             }
         } else {
@@ -285,10 +511,11 @@ impl Parser {
     fn or(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.and()?;
 
-        while self.match_(&vec![TokenType::And]) {
+        while self.match_(&vec![TokenType::Or]) {
             let operator = self.previous();
-            let right = self.equality()?;
-            expr = Expr::Logical(Box::new(expr), operator, Box::new(right));
+            let right = self.and()?;
+            let span = expr.span.to(right.span);
+            expr = Expr::new(ExprKind::Logical(Box::new(expr), operator, Box::new(right)), span);
         }
 
         Ok(expr)
@@ -300,7 +527,8 @@ impl Parser {
         while self.match_(&vec![TokenType::And]) {
             let operator = self.previous();
             let right = self.equality()?;
-            expr = Expr::Logical(Box::new(expr), operator, Box::new(right));
+            let span = expr.span.to(right.span);
+            expr = Expr::new(ExprKind::Logical(Box::new(expr), operator, Box::new(right)), span);
         }
 
         Ok(expr)
@@ -313,7 +541,8 @@ impl Parser {
         while self.match_(&token_types) {
             let operator = self.previous();
             let right = self.comparison()?;
-            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+            let span = expr.span.to(right.span);
+            expr = Expr::new(ExprKind::Binary(Box::new(expr), operator, Box::new(right)), span);
         }
 
         Ok(expr)
@@ -326,7 +555,8 @@ impl Parser {
         while self.match_(&token_types) {
             let operator = self.previous();
             let right = self.term()?;
-            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+            let span = expr.span.to(right.span);
+            expr = Expr::new(ExprKind::Binary(Box::new(expr), operator, Box::new(right)), span);
         }
 
         Ok(expr)
@@ -338,8 +568,9 @@ impl Parser {
         let token_types = vec![TokenType::Minus, TokenType::Plus];
         while self.match_(&token_types) {
             let operator = self.previous();
-            let right = self.unary()?;
-            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+            let right = self.factor()?;
+            let span = expr.span.to(right.span);
+            expr = Expr::new(ExprKind::Binary(Box::new(expr), operator, Box::new(right)), span);
         }
 
         Ok(expr)
@@ -352,7 +583,8 @@ impl Parser {
         while self.match_(&token_types) {
             let operator = self.previous();
             let right = self.unary()?;
-            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+            let span = expr.span.to(right.span);
+            expr = Expr::new(ExprKind::Binary(Box::new(expr), operator, Box::new(right)), span);
         }
 
         Ok(expr)
@@ -362,37 +594,96 @@ impl Parser {
         let token_types = vec![TokenType::Bang, TokenType::Minus];
         if self.match_(&token_types) {
             let operator = self.previous();
-            let right = self.unary()?;
-            return Ok(Expr::Unary(operator, Box::new(right)));
+            let right = self.nested(Self::unary)?;
+            let span = operator.span.to(right.span);
+            return Ok(Expr::new(ExprKind::Unary(operator, Box::new(right)), span));
+        }
+
+        self.call()
+    }
+
+    fn call(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.match_(&vec![TokenType::LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_(&vec![TokenType::Dot]) {
+                let name = self.consume(TokenType::Identifier, "Expect property name after '.'.".to_owned())?;
+                let span = expr.span.to(name.span);
+                expr = Expr::new(ExprKind::Get(Box::new(expr), name), span);
+            } else {
+                break;
+            }
         }
 
-        self.primary()
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, ParseError> {
+        let mut arguments = Vec::new();
+
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    self.diagnostics.push(Diagnostic::from(ParseError(self.peek().clone(), format!("Can't have more than {} arguments.", MAX_ARGUMENTS))));
+                }
+
+                arguments.push(self.expression()?);
+
+                if !self.match_(&vec![TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.".to_owned())?;
+        let span = callee.span.to(paren.span);
+        Ok(Expr::new(ExprKind::Call(Box::new(callee), paren, arguments), span))
     }
 
     fn primary(&mut self) -> Result<Expr, ParseError> {
         if self.match_(&vec![TokenType::False]) {
-            return Ok(Expr::Literal(Literal::Bool(false)));
+            return Ok(Expr::new(ExprKind::Literal(Literal::Bool(false)), self.previous().span));
         }
         if self.match_(&vec![TokenType::True]) {
-            return Ok(Expr::Literal(Literal::Bool(true)));
+            return Ok(Expr::new(ExprKind::Literal(Literal::Bool(true)), self.previous().span));
         }
         if self.match_(&vec![TokenType::Nil]) {
-            return Ok(Expr::Literal(Literal::Nil));
+            return Ok(Expr::new(ExprKind::Literal(Literal::Nil), self.previous().span));
         }
 
         if self.match_(&vec![TokenType::Number, TokenType::String]) {
-            return Ok(Expr::Literal(self.previous().literal.ok_or_else(|| ParseError(self.peek().clone(), "".to_owned())).unwrap()));
+            let token = self.previous();
+            return Ok(Expr::new(ExprKind::Literal(token.literal.ok_or_else(|| ParseError(self.peek().clone(), "".to_owned())).unwrap()), token.span));
+        }
+
+        if self.match_(&vec![TokenType::Super]) {
+            let keyword = self.previous();
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.".to_owned())?;
+            let method = self.consume(TokenType::Identifier, "Expect superclass method name.".to_owned())?;
+            let span = keyword.span.to(method.span);
+            return Ok(Expr::new(ExprKind::Super(keyword, method, Cell::new(None)), span))
+        }
+
+        if self.match_(&vec![TokenType::This]) {
+            let keyword = self.previous();
+            let span = keyword.span;
+            return Ok(Expr::new(ExprKind::This(keyword, Cell::new(None)), span))
         }
 
         if self.match_(&vec![TokenType::Identifier]) {
-            return Ok(Expr::Var(self.previous()))
+            let name = self.previous();
+            let span = name.span;
+            return Ok(Expr::new(ExprKind::Var(name, Cell::new(None)), span))
         }
 
         if self.match_(&vec![TokenType::LeftParen]) {
+            let start = self.previous().span;
             let expr = self.expression()?;
             let right_paren_exists = self.consume(TokenType::RightParen, "Expect ')' after expression.".to_owned());
             match right_paren_exists {
-                Ok(_) => return Ok(Expr::Grouping(Box::new(expr))),
+                Ok(_) => return Ok(Expr::new(ExprKind::Grouping(Box::new(expr)), self.span_from(start))),
                 Err(e) => return Err(e),
             };
         }
@@ -410,7 +701,12 @@ impl Parser {
     }
 
     fn previous(&self) -> Token {
-        (&self.tokens[self.current-1]).clone()
+        self.tokens[self.current-1].clone()
+    }
+
+    // from start up to the end of the last consumed token
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.tokens[self.current-1].span)
     }
 
     fn at_end(&self) -> bool {
@@ -444,6 +740,19 @@ impl Parser {
         false
     }
 
+    // parses with a rule that recurses, giving up once it has recursed too far
+    fn nested<T>(&mut self, rule: fn(&mut Self) -> Result<T, ParseError>) -> Result<T, ParseError> {
+        if self.depth == MAX_NESTING {
+            return Err(ParseError(self.peek().clone(), "Too much nesting.".to_owned()));
+        }
+
+        self.depth += 1;
+        let result = stack::guarded(|| rule(self));
+        self.depth -= 1;
+
+        result
+    }
+
     fn consume(&mut self, token_type: TokenType, message: String) -> Result<Token, ParseError> {
         if self.check(&token_type) {
             Ok(self.advance())
@@ -459,7 +768,7 @@ pub struct ParseError(pub Token, pub String);
 
 impl std::fmt::Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}, {}", self.0.to_string(), self.1)
+        write!(f, "{}, {}", self.0, self.1)
     }
 }
 
@@ -0,0 +1,280 @@
+use crate::{diagnostic::Diagnostic, parser::{Expr, ExprKind, FunctionDecl, Stmt, StmtKind}, stack, token::{Span, Token}};
+
+use std::{cell::Cell, collections::HashMap, error};
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+// walks the syntax tree once before it's interpreted, working out which
+// declaration every variable refers to. since nothing here has side effects,
+// every branch and loop body is visited exactly once
+pub struct Resolver {
+    // each scope maps a name to whether its initializer has finished resolving,
+    // and where it was declared. the global scope isn't tracked, anything not
+    // found here is a global
+    scopes: Vec<HashMap<String, (bool, Span)>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver {
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    // fills in the depth of every local variable in stmts, returning any errors found along the way
+    pub fn resolve(&mut self, stmts: &[Stmt]) -> Vec<Diagnostic> {
+        self.resolve_stmts(stmts);
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    fn resolve_stmts(&mut self, stmts: &[Stmt]) {
+        for s in stmts {
+            self.resolve_stmt(s);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        stack::guarded(|| self.resolve_stmt_kind(stmt))
+    }
+
+    fn resolve_stmt_kind(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Block(stmts) => {
+                self.begin_scope();
+                self.resolve_stmts(stmts);
+                self.end_scope();
+            },
+            StmtKind::Var(name, initializer) => {
+                // declared and defined in two steps so that an initializer
+                // referring to the variable itself can be caught
+                self.declare(name);
+                if let Some(e) = &**initializer {
+                    self.resolve_expr(e);
+                }
+                self.define(name);
+            },
+            StmtKind::Function(declaration) => {
+                // defined eagerly so the function can refer to itself recursively
+                self.declare(&declaration.name);
+                self.define(&declaration.name);
+
+                self.resolve_function(declaration, FunctionType::Function);
+            },
+            StmtKind::Class(name, superclass, methods) => {
+                let enclosing_class = self.current_class;
+                self.current_class = ClassType::Class;
+
+                self.declare(name);
+                self.define(name);
+
+                if let Some(s) = superclass {
+                    if let ExprKind::Var(superclass_name, _) = &s.kind {
+                        if superclass_name.lexeme == name.lexeme {
+                            self.diagnostics.push(ResolveError(superclass_name.clone(), "A class can't inherit from itself.".to_owned()).into());
+                        }
+                    }
+
+                    self.current_class = ClassType::Subclass;
+                    self.resolve_expr(s);
+
+                    self.begin_scope();
+                    self.scopes.last_mut().unwrap().insert("super".to_owned(), (true, s.span));
+                }
+
+                // methods close over a scope binding "this", mirroring LoxFunction::bind
+                self.begin_scope();
+                self.scopes.last_mut().unwrap().insert("this".to_owned(), (true, name.span));
+
+                for m in methods {
+                    let function_type = if m.name.lexeme == "init" { FunctionType::Initializer } else { FunctionType::Method };
+                    self.resolve_function(m, function_type);
+                }
+
+                self.end_scope();
+
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+
+                self.current_class = enclosing_class;
+            },
+            StmtKind::Expr(e) | StmtKind::Print(e) => {
+                self.resolve_expr(e);
+            },
+            StmtKind::If(condition, then_branch, else_branch) => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(then_branch);
+                if let Some(e) = else_branch {
+                    self.resolve_stmt(e);
+                }
+            },
+            StmtKind::While(condition, body) => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(body);
+            },
+            StmtKind::Return(keyword, value) => {
+                if self.current_function == FunctionType::None {
+                    let error = Diagnostic::from(ResolveError(keyword.clone(), "Can't return from top-level code.".to_owned()));
+                    self.diagnostics.push(error.with_note("return can only be used inside a function or method".to_owned()));
+                }
+
+                if let Some(e) = value {
+                    if self.current_function == FunctionType::Initializer {
+                        self.diagnostics.push(ResolveError(keyword.clone(), "Can't return a value from an initializer.".to_owned()).into());
+                    }
+
+                    self.resolve_expr(e);
+                }
+            },
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        stack::guarded(|| self.resolve_expr_kind(expr))
+    }
+
+    fn resolve_expr_kind(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Var(name, depth) => {
+                if let Some((false, declaration)) = self.scopes.last().and_then(|s| s.get(&*name.lexeme)) {
+                    let error = Diagnostic::from(ResolveError(name.clone(), "Can't read local variable in its own initializer.".to_owned()));
+                    self.diagnostics.push(error.with_secondary(*declaration, "variable declared here".to_owned()));
+                }
+
+                self.resolve_local(name, depth);
+            },
+            ExprKind::Assignment(name, value, depth) => {
+                self.resolve_expr(value);
+                self.resolve_local(name, depth);
+            },
+            ExprKind::Binary(l, _, r) | ExprKind::Logical(l, _, r) => {
+                self.resolve_expr(l);
+                self.resolve_expr(r);
+            },
+            ExprKind::Call(callee, _, arguments) => {
+                self.resolve_expr(callee);
+                for a in arguments {
+                    self.resolve_expr(a);
+                }
+            },
+            ExprKind::Get(object, _) => {
+                self.resolve_expr(object);
+            },
+            ExprKind::Set(object, _, value) => {
+                self.resolve_expr(value);
+                self.resolve_expr(object);
+            },
+            ExprKind::This(keyword, depth) => {
+                if self.current_class == ClassType::None {
+                    self.diagnostics.push(ResolveError(keyword.clone(), "Can't use 'this' outside of a class.".to_owned()).into());
+                    return;
+                }
+
+                self.resolve_local(keyword, depth);
+            },
+            ExprKind::Super(keyword, _, depth) => {
+                match self.current_class {
+                    ClassType::None => {
+                        self.diagnostics.push(ResolveError(keyword.clone(), "Can't use 'super' outside of a class.".to_owned()).into());
+                    },
+                    ClassType::Class => {
+                        self.diagnostics.push(ResolveError(keyword.clone(), "Can't use 'super' in a class with no superclass.".to_owned()).into());
+                    },
+                    ClassType::Subclass => {
+                        self.resolve_local(keyword, depth);
+                    }
+                }
+            },
+            ExprKind::Unary(_, e) | ExprKind::Grouping(e) => {
+                self.resolve_expr(e);
+            },
+            ExprKind::Literal(_) => {},
+        }
+    }
+
+    fn resolve_function(&mut self, declaration: &FunctionDecl, function_type: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+
+        self.begin_scope();
+        for p in &declaration.params {
+            self.declare(p);
+            self.define(p);
+        }
+        self.resolve_stmts(&declaration.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    fn resolve_local(&mut self, name: &Token, depth: &Cell<Option<usize>>) {
+        for (i, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(&*name.lexeme) {
+                depth.set(Some(i));
+                return;
+            }
+        }
+    }
+
+    // ======== SCOPES ========
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            if let Some((_, previous)) = scope.get(&*name.lexeme) {
+                let error = Diagnostic::from(ResolveError(name.clone(), "Already a variable with this name in this scope.".to_owned()));
+                self.diagnostics.push(error.with_secondary(*previous, "previously declared here".to_owned()));
+            }
+
+            scope.insert(name.lexeme.to_string(), (false, name.span));
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.to_string(), (true, name.span));
+        }
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Resolver::new()
+    }
+}
+
+#[derive(Debug)]
+pub struct ResolveError(pub Token, pub String);
+
+impl std::fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}, {}", self.0, self.1)
+    }
+}
+
+impl error::Error for ResolveError {}
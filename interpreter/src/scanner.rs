@@ -1,15 +1,23 @@
-use crate::Lox;
+use crate::diagnostic::{Diagnosed, Diagnostic};
 
 use lazy_static::lazy_static;
-use super::token::{Token, TokenType, Literal};
-use std::collections::HashMap;
+use super::token::{Token, TokenType, Literal, Lexeme, Span, Trivia, TriviaKind};
+use std::{collections::HashMap, rc::Rc};
+use unicode_xid::UnicodeXID;
 
 // ======== LEXICAL GRAMMAR ========
 // NUMBER         → DIGIT+ ( "." DIGIT+ )? ;
-// STRING         → "\"" <any char except "\"">* "\"" ;
+// STRING         → "\"" ( <any char except "\"" or "\\"> | ESCAPE )* "\"" ;
+// ESCAPE         → "\\" ( "n" | "t" | "r" | "0" | "\\" | "\"" | "u{" HEX_DIGIT+ "}" ) ;
+// COMMENT        → "//" <any char except newline>* | "/*" ( <any text> | COMMENT )* "*/" ;
+// DOC_COMMENT    → "///" <any char except newline>* ;  kept as trivia on the next token
+// RAW_STRING     → "r" "#"* "\"" <any text> "\"" "#"* ;  with the same number of #s either side
 // IDENTIFIER     → ALPHA ( ALPHA | DIGIT )* ;
 // ALPHA          → "a" ... "z" | "A" ... "Z" | "_" ;
 // DIGIT          → "0" ... "9" ;
+//
+// with IdentifierRule::Unicode, identifiers follow UAX #31 instead:
+// IDENTIFIER     → ( XID_Start | "_" ) XID_Continue* ;
 // =================================
 
 lazy_static! {
@@ -36,37 +44,96 @@ lazy_static! {
     };
 }
 
+// which characters identifiers can be made of. strings and comments can hold any text either way
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdentifierRule {
+    // letters, digits and underscores from ASCII, like the book
+    Ascii,
+    // letters and digits from any script, following Unicode's XID_Start and XID_Continue
+    Unicode,
+}
+
+// scans the source in a single pass. positions are byte offsets into it, so
+// peeking at and stepping over a character is constant time whatever its
+// encoded length
 pub struct Scanner {
-    source: String,
+    source: Rc<str>,
     tokens: Vec<Token>,
+    trivia: Vec<Trivia>, // waiting for the next token
+    lossless: bool,
+    covered: usize, // how far tokens and trivia reach into the source
+    diagnostics: Vec<Diagnostic>,
     start: usize,
     current: usize,
-    line: u32
+    line: u32,
+    column: u32, // of current, counted in characters
+    // where the token being scanned starts, which for multi-line strings isn't the line it ends on
+    start_line: u32,
+    start_column: u32,
+    identifier_rule: IdentifierRule,
 }
 
 impl Scanner {
-    pub fn new(source: String) -> Self {
+    pub fn new<S: Into<Rc<str>>>(source: S) -> Self {
         Scanner {
-            source,
+            source: source.into(),
             tokens: Vec::new(),
+            trivia: Vec::new(),
+            lossless: false,
+            covered: 0,
+            diagnostics: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
+            column: 1,
+            start_line: 1,
+            start_column: 1,
+            identifier_rule: IdentifierRule::Ascii,
         }
     }
-    
+
+    pub fn set_identifier_rule(&mut self, rule: IdentifierRule) {
+        self.identifier_rule = rule;
+    }
+
+    // keeps whitespace, newlines, comments and text that couldn't be scanned
+    // as trivia too, so that the text of each token's trivia followed by the
+    // token's own adds back up to the source exactly
+    pub fn set_lossless(&mut self, lossless: bool) {
+        self.lossless = lossless;
+    }
+
     fn at_end(&self) -> bool {
         self.current >= self.source.len()
     }
 
-    pub fn scan_tokens(&mut self) -> Vec<Token> {
+    pub fn scan_tokens(&mut self) -> Diagnosed<Vec<Token>> {
+        // lexemes store their offsets as u32s, see token::Lexeme
+        if self.source.len() > u32::MAX as usize {
+            self.diagnostics.push(Diagnostic::new("Source is longer than 4GiB.".to_owned(), Span::new(0, 0), Rc::clone(&self.source)));
+        } else {
+            self.scan_all();
+        }
+
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column;
+        self.add_full_token(TokenType::Eof, None);
+        Diagnosed::new(std::mem::take(&mut self.tokens), std::mem::take(&mut self.diagnostics))
+    }
+
+    fn scan_all(&mut self) {
         while !self.at_end() {
             self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column;
             self.scan_token();
-        }
 
-        self.tokens.push(Token::new(TokenType::Eof, "".to_owned(), None, self.line));
-        self.tokens.clone()
+            // whatever was scanned after an error didn't make it into a token
+            if self.covered < self.current {
+                self.add_trivia(TriviaKind::Skipped);
+            }
+        }
     }
 
 
@@ -104,23 +171,45 @@ impl Scanner {
             },
             '/' => {
                 if self.match_('/') {
-                    while self.peek() != '\n' && !self.at_end() {
+                    // a third slash makes it a doc comment, a fourth makes it an ordinary comment again
+                    let doc = self.peek() == '/' && self.peek_next() != '/';
+
+                    // the \r of a \r\n is part of the newline, not the comment
+                    while self.peek() != '\n' && !(self.peek() == '\r' && self.peek_next() == '\n') && !self.at_end() {
                         self.advance();
                     }
+
+                    self.add_trivia(if doc { TriviaKind::DocComment } else { TriviaKind::LineComment });
+                } else if self.match_('*') {
+                    self.block_comment();
                 } else {
                     self.add_token(TokenType::Slash)
                 }
             },
-            ' ' | '\r' | '\t' => {},
-            '\n' => self.line += 1,
+            '\r' if self.match_('\n') => {
+                self.newline();
+                self.add_trivia(TriviaKind::Newline);
+            },
+            ' ' | '\r' | '\t' => {
+                while matches!(self.peek(), ' ' | '\t') || (self.peek() == '\r' && self.peek_next() != '\n') {
+                    self.advance();
+                }
+                self.add_trivia(TriviaKind::Whitespace);
+            },
+            '\n' => {
+                self.newline();
+                self.add_trivia(TriviaKind::Newline);
+            },
             '"' => self.string(),
             c => {
-                if c.is_digit(10) { // nesting digit arm in default to avoid messy '1' => {}, '2' => {}...
+                if c.is_ascii_digit() { // nesting digit arm in default to avoid messy '1' => {}, '2' => {}...
                     self.number();
-                } else if c.is_alphabetic() {
+                } else if let Some(hashes) = self.raw_string_prefix(c) {
+                    self.raw_string(hashes);
+                } else if self.is_identifier_start(c) {
                     self.identifier()
                 } else {
-                    Lox::error(self.line, "Unexpected character.".to_owned())
+                    self.error("Unexpected character.")
                 }
             },
         }
@@ -131,98 +220,271 @@ impl Scanner {
     }
 
     fn add_full_token(&mut self, token_type: TokenType, literal: Option<Literal>) {
-        let text: String = self.source.chars().skip(self.start).take(self.current - self.start).collect();
+        let span = Span::new(self.start, self.current);
 
-        self.tokens.push(Token::new(
+        let mut token = Token::new(
             token_type,
-            text,
+            Lexeme::new(Rc::clone(&self.source), span),
             literal,
-            self.line,
-        ))
+            self.start_line,
+            self.start_column,
+            span,
+        );
+        token.trivia = std::mem::take(&mut self.trivia).into_boxed_slice();
+        self.covered = self.current;
+
+        self.tokens.push(token)
+    }
+
+    // holds on to what was just scanned until the next token, which it's attached to.
+    // outside of lossless mode only doc comments are worth keeping
+    fn add_trivia(&mut self, kind: TriviaKind) {
+        self.covered = self.current;
+        if !self.lossless && kind != TriviaKind::DocComment {
+            return;
+        }
+
+        let span = Span::new(self.start, self.current);
+
+        self.trivia.push(Trivia {
+            kind,
+            text: Lexeme::new(Rc::clone(&self.source), span),
+            span,
+        });
+    }
+
+    // the text of the token scanned so far
+    fn text(&self) -> &str {
+        &self.source[self.start..self.current]
+    }
+
+    // reports an error covering the token scanned so far
+    fn error(&mut self, message: &str) {
+        self.error_from(self.start, message);
+    }
+
+    // reports an error covering just the part of the token from start on
+    fn error_from(&mut self, start: usize, message: &str) {
+        let span = Span::new(start, self.current);
+        self.diagnostics.push(Diagnostic::new(message.to_owned(), span, Rc::clone(&self.source)));
+    }
+
+    fn newline(&mut self) {
+        self.line += 1;
+        self.column = 1;
     }
 
     // ========= COMBINATORS ========
     fn peek(&self) -> char {
-        if self.at_end() {
-            '\0'
-        } else {
-            self.source.chars().nth(self.current).unwrap()
-        }
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            '\0'
-        } else {
-            self.source.chars().nth(self.current + 1).unwrap()
-        }
+        self.source[self.current..].chars().nth(1).unwrap_or('\0')
     }
 
     fn advance(&mut self) -> char {
-        let c = self.source.chars().nth(self.current).unwrap();
-        self.current += 1;
+        let c = self.peek();
+        self.current += c.len_utf8();
+        self.column += 1;
 
         c
     }
 
     fn match_(&mut self, expected: char) -> bool {
-        if self.at_end() {
+        if self.at_end() || self.peek() != expected {
             return false;
         }
 
-        if self.source.chars().nth(self.current).unwrap() != expected {
-            return false;
-        }
-
-        self.current += 1;
+        self.advance();
         true
     }
 
     fn string(&mut self) {
+        // the literal value leaves out the surrounding quotes, and has its escapes decoded
+        let mut value = String::new();
+
         while self.peek() != '"' && !self.at_end() {
-            if self.peek() == '\n' { // Lox supports multi-line strings
-                self.line += 1
+            match self.advance() {
+                // bad escapes are left out, the string is still a token so the parser has something to work with
+                '\\' => value.extend(self.escape()),
+                c => {
+                    if c == '\n' { // Lox supports multi-line strings
+                        self.newline();
+                    }
+                    value.push(c);
+                },
             }
-            self.advance();
         }
 
         if self.at_end() {
-            Lox::error(self.line, "Unterminated string.".to_owned());
-            return ();
+            self.error("Unterminated string.");
+            return;
         }
 
         // the closing ".
         self.advance();
 
-        let literal = Literal::String(self.source.chars().skip(self.start).take(self.current - self.start).collect::<String>());
-        self.add_full_token(TokenType::String, Some(literal));
+        self.add_full_token(TokenType::String, Some(Literal::String(value)));
+    }
+
+    // decodes the escape sequence after a backslash, reporting it if it isn't one
+    fn escape(&mut self) -> Option<char> {
+        let start = self.current - 1;
+        if self.at_end() {
+            return None; // reported as an unterminated string
+        }
+
+        match self.advance() {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '\\' => Some('\\'),
+            '"' => Some('"'),
+            'u' => self.unicode_escape(start),
+            c => {
+                if c == '\n' {
+                    self.newline();
+                }
+
+                self.error_from(start, &format!("Unknown escape sequence '\\{}'.", c.escape_default()));
+                None
+            }
+        }
+    }
+
+    // \u{XXXX}, where the digits are the hex code point of any character
+    fn unicode_escape(&mut self, start: usize) -> Option<char> {
+        if !self.match_('{') {
+            self.error_from(start, "Expect '{' after '\\u'.");
+            return None;
+        }
+
+        let digits_start = self.current;
+        while self.peek().is_ascii_hexdigit() {
+            self.advance();
+        }
+        let digits = self.source[digits_start..self.current].to_owned();
+
+        if !self.match_('}') {
+            self.error_from(start, "Expect '}' after unicode escape digits.");
+            return None;
+        }
+
+        if digits.is_empty() || digits.len() > 6 {
+            self.error_from(start, "Unicode escapes take 1 to 6 hex digits.");
+            return None;
+        }
+
+        // surrogates and anything past 10FFFF aren't characters
+        let c = u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32);
+        if c.is_none() {
+            self.error_from(start, &format!("'\\u{{{}}}' is not a valid character.", digits));
+        }
+
+        c
+    }
+
+    // the number of #s in a raw string's opening delimiter, if c starts one
+    fn raw_string_prefix(&self, c: char) -> Option<usize> {
+        if c != 'r' {
+            return None;
+        }
+
+        let rest = &self.source[self.current..];
+        let hashes = rest.len() - rest.trim_start_matches('#').len();
+
+        if rest[hashes..].starts_with('"') {
+            Some(hashes)
+        } else {
+            None
+        }
+    }
+
+    // taken as is, with no escapes. any #s after the r have to follow the
+    // closing quote too, so that strings with quotes in them can be written
+    fn raw_string(&mut self, hashes: usize) {
+        // the #s and the opening "
+        for _ in 0..=hashes {
+            self.advance();
+        }
+
+        let value_start = self.current;
+        let closing = format!("\"{}", "#".repeat(hashes));
+
+        while !self.source[self.current..].starts_with(&closing) {
+            if self.at_end() {
+                self.error("Unterminated raw string.");
+                return;
+            }
+
+            if self.advance() == '\n' {
+                self.newline();
+            }
+        }
+
+        let value = self.source[value_start..self.current].to_owned();
+        for _ in 0..closing.len() {
+            self.advance();
+        }
+
+        self.add_full_token(TokenType::String, Some(Literal::String(value)));
+    }
+
+    // block comments nest, so one can be used to comment out code that already has them
+    fn block_comment(&mut self) {
+        let mut depth = 1;
+
+        while depth > 0 {
+            if self.at_end() {
+                // the outermost comment is the one left open, report where it starts
+                let opening = Span::new(self.start, self.start + 2);
+                self.diagnostics.push(Diagnostic::new("Unterminated block comment.".to_owned(), opening, Rc::clone(&self.source)));
+                break;
+            }
+
+            if self.match_('/') {
+                if self.match_('*') {
+                    depth += 1;
+                }
+            } else if self.match_('*') {
+                if self.match_('/') {
+                    depth -= 1;
+                }
+            } else if self.advance() == '\n' {
+                self.newline();
+            }
+        }
+
+        self.add_trivia(TriviaKind::BlockComment);
     }
 
     fn number(&mut self) {
-        while self.peek().is_digit(10) {
+        while self.peek().is_ascii_digit() {
             self.advance();
         }
 
         // look for a fractional part
-        if self.peek() == '.' && self.peek_next().is_digit(10) {
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             // consume the "."
             self.advance();
 
-            while self.peek().is_digit(10) {
+            while self.peek().is_ascii_digit() {
                 self.advance();
             }
         }
 
-        self.add_full_token(TokenType::Number, Some(Literal::Number(self.source.chars().skip(self.start).take(self.current - self.start).collect::<String>().parse::<f64>().unwrap())));
+        let value = self.text().parse::<f64>().unwrap();
+        self.add_full_token(TokenType::Number, Some(Literal::Number(value)));
     }
 
     fn identifier(&mut self) {
-        while self.peek().is_alphanumeric() {
+        while self.is_identifier_continue(self.peek()) {
             self.advance();
         }
 
-        let text = self.source.chars().skip(self.start).take(self.current - self.start).collect::<String>();
-        let keyword_lookup = KEYWORDS.get(&text);
+        let keyword_lookup = KEYWORDS.get(self.text());
         let token_type = match keyword_lookup {
             Some(t) => {
                 t
@@ -232,4 +494,18 @@ impl Scanner {
 
         self.add_token(token_type.to_owned());
     }
-}
\ No newline at end of file
+
+    fn is_identifier_start(&self, c: char) -> bool {
+        match self.identifier_rule {
+            IdentifierRule::Ascii => c.is_ascii_alphabetic() || c == '_',
+            IdentifierRule::Unicode => UnicodeXID::is_xid_start(c) || c == '_',
+        }
+    }
+
+    fn is_identifier_continue(&self, c: char) -> bool {
+        match self.identifier_rule {
+            IdentifierRule::Ascii => c.is_ascii_alphanumeric() || c == '_',
+            IdentifierRule::Unicode => UnicodeXID::is_xid_continue(c),
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{diagnostic::Diagnostic, interpreter::Interpreter, parser::Parser, resolver::Resolver, scanner::{IdentifierRule, Scanner}, value::Value};
+
+use std::{error, fmt, fs, io::{self, Write}, path::Path};
+
+// one running Lox program. globals, classes and functions defined by one
+// eval are there for the next, like lines typed into the REPL.
+//
+// sessions don't share anything, so any number of them can run side by side.
+// values hold on to Rc's though, so a session stays on the thread that made it
+pub struct Session {
+    interpreter: Interpreter,
+    diagnostic_output: Box<dyn Write>,
+    color: bool,
+    error_format: ErrorFormat,
+    identifier_rule: IdentifierRule,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Session {
+            interpreter: Interpreter::new(),
+            diagnostic_output: Box::new(io::sink()),
+            color: false,
+            error_format: ErrorFormat::Rich,
+            identifier_rule: IdentifierRule::Ascii,
+        }
+    }
+
+    // runs source, returning the value of its last statement if that's an
+    // expression statement, and nil otherwise
+    pub fn eval(&mut self, source: &str) -> Result<Value, Error> {
+        let result = self.run(source);
+
+        if let Err(e) = &result {
+            // like print output, errors that can't be written are dropped
+            let rendered = match self.error_format {
+                ErrorFormat::Rich => e.render(self.color),
+                ErrorFormat::Short => e.render_short(),
+            };
+            let _ = write!(self.diagnostic_output, "{}", rendered);
+        }
+
+        result
+    }
+
+    fn run(&mut self, source: &str) -> Result<Value, Error> {
+        let mut scanner = Scanner::new(source);
+        scanner.set_identifier_rule(self.identifier_rule);
+
+        let scanned = scanner.scan_tokens();
+        let parsed = Parser::new(scanned.value).parse();
+
+        // scan errors don't stop the parser, so both get reported together
+        let mut diagnostics = scanned.diagnostics;
+        diagnostics.extend(parsed.diagnostics);
+        if !diagnostics.is_empty() {
+            return Err(Error::Compile(diagnostics));
+        }
+
+        let stmts = parsed.value;
+        let diagnostics = Resolver::new().resolve(&stmts);
+        if !diagnostics.is_empty() {
+            return Err(Error::Compile(diagnostics));
+        }
+
+        self.interpreter.interpret(stmts).map_err(|e| Error::Runtime(Box::new(e.into())))
+    }
+
+    pub fn run_file<P: AsRef<Path>>(&mut self, path: P) -> Result<Value, Error> {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                let _ = writeln!(self.diagnostic_output, "error: {}", e);
+                return Err(Error::Io(e));
+            }
+        };
+
+        self.eval(&source)
+    }
+
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.interpreter.define_global(name, value);
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.interpreter.global(name)
+    }
+
+    // see Interpreter::define_native
+    pub fn define_native<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(&[Value]) -> Result<Value, String> + 'static,
+    {
+        self.interpreter.define_native(name, arity, function);
+    }
+
+    // where print statements write to, stdout by default
+    pub fn set_output<W: Write + 'static>(&mut self, output: W) {
+        self.interpreter.set_output(Box::new(output));
+    }
+
+    // where errors are rendered to as they happen, on top of being returned.
+    // by default they're only returned
+    pub fn set_diagnostic_output<W: Write + 'static>(&mut self, output: W, color: bool) {
+        self.diagnostic_output = Box::new(output);
+        self.color = color;
+    }
+
+    pub fn set_error_format(&mut self, format: ErrorFormat) {
+        self.error_format = format;
+    }
+
+    // applies to source evaluated from then on
+    pub fn set_identifier_rule(&mut self, rule: IdentifierRule) {
+        self.identifier_rule = rule;
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session::new()
+    }
+}
+
+// how errors written to the diagnostic output look
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    // rustc-style, with the offending source underlined
+    Rich,
+    // one line per error, see Error::render_short
+    Short,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    // the program was rejected before any of it ran
+    Compile(Vec<Diagnostic>),
+    Runtime(Box<Diagnostic>),
+    Io(io::Error),
+}
+
+impl Error {
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        match self {
+            Error::Compile(diagnostics) => diagnostics,
+            Error::Runtime(diagnostic) => std::slice::from_ref(diagnostic),
+            Error::Io(_) => &[],
+        }
+    }
+
+    pub fn render(&self, color: bool) -> String {
+        match self {
+            Error::Io(e) => format!("error: {}\n", e),
+            _ => self.diagnostics().iter().map(|d| d.render(color)).collect(),
+        }
+    }
+
+    // how the reference implementations print errors: one line per compile
+    // error, and a runtime error's message followed by the line it happened on
+    pub fn render_short(&self) -> String {
+        match self {
+            Error::Compile(diagnostics) => diagnostics.iter().map(|d| d.render_short()).collect(),
+            Error::Runtime(d) => format!("{}\n[line {}]\n", d.message, d.line()),
+            Error::Io(e) => format!("error: {}\n", e),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            _ => {
+                let messages: Vec<&str> = self.diagnostics().iter().map(|d| d.message.as_str()).collect();
+                write!(f, "{}", messages.join("\n"))
+            }
+        }
+    }
+}
+
+impl error::Error for Error {}
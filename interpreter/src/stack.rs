@@ -0,0 +1,15 @@
+// parsing, resolving and interpreting all recurse over the syntax tree, and
+// lox calls recurse through the interpreter, so the native stack a program
+// needs grows with how deeply it nests and recurses. rather than count on the
+// host's thread having enough, the recursive functions go through guarded,
+// which carries on on a new stack from the heap when the current one is close
+// to running out. the depth limits in the parser and interpreter bound how
+// much that can be
+
+// more than any one step of the recursion uses between checks, even in a debug build
+const RED_ZONE: usize = 256 * 1024;
+const SEGMENT_SIZE: usize = 4 * 1024 * 1024;
+
+pub fn guarded<R, F: FnOnce() -> R>(f: F) -> R {
+    stacker::maybe_grow(RED_ZONE, SEGMENT_SIZE, f)
+}
@@ -1,9 +1,132 @@
+use std::{convert::TryFrom, fmt, ops::Deref, rc::Rc};
+
 #[derive(Debug, Clone)]
 pub struct Token {
     pub token_type: TokenType,
-    pub lexeme: String,
+    pub lexeme: Lexeme,
     pub literal: Option<Literal>,
     pub line: u32,
+    pub column: u32, // 1-based, counted in characters
+    pub span: Span,
+    // what came between this token and the one before it, for tools that care about more than the program's meaning
+    pub trivia: Box<[Trivia]>,
+}
+
+// a range of the source, as byte offsets. start is inclusive and end is exclusive
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span {
+            start,
+            end,
+        }
+    }
+
+    // the smallest span covering both self and other
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+// source text between tokens that doesn't change what the program means
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: Lexeme,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TriviaKind {
+    // a run of spaces and tabs
+    Whitespace,
+    // \n or \r\n
+    Newline,
+    LineComment,
+    BlockComment,
+    DocComment,
+    // text the scanner reported an error for rather than making a token of it
+    Skipped,
+}
+
+// a token's text. tokens share the source they were scanned from and each
+// keeps only its own range of it, so scanning doesn't copy any text
+#[derive(Clone)]
+pub struct Lexeme {
+    source: Rc<str>,
+    // u32s rather than a Span keep tokens small, which limits sources to 4GiB
+    start: u32,
+    end: u32,
+}
+
+impl Lexeme {
+    // the scanner turns down longer sources, so this only panics when called directly
+    pub fn new(source: Rc<str>, span: Span) -> Self {
+        Lexeme {
+            source,
+            start: u32::try_from(span.start).expect("lexemes can't start past 4GiB"),
+            end: u32::try_from(span.end).expect("lexemes can't end past 4GiB"),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source[self.start as usize..self.end as usize]
+    }
+
+    // all of the source the lexeme is a part of
+    pub fn source(&self) -> &Rc<str> {
+        &self.source
+    }
+}
+
+// for tokens that don't come from any source, like the implicit "this" of a method
+impl From<&str> for Lexeme {
+    fn from(text: &str) -> Self {
+        Lexeme::new(text.into(), Span::new(0, text.len()))
+    }
+}
+
+impl Deref for Lexeme {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for Lexeme {
+    fn eq(&self, other: &Lexeme) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl PartialEq<str> for Lexeme {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Lexeme {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for Lexeme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Lexeme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -29,19 +152,38 @@ pub enum TokenType {
 }
 
 
-impl ToString for Token {
-    fn to_string(&self) -> String {
-        format!("{:?} {} {:?}", self.token_type, self.lexeme, self.literal)
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} {} {:?} at {}:{}", self.token_type, self.lexeme, self.literal, self.line, self.column)
     }
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, lexeme: String, literal: Option<Literal>, line: u32) -> Self {
+    pub fn new(token_type: TokenType, lexeme: Lexeme, literal: Option<Literal>, line: u32, column: u32, span: Span) -> Self {
         Token {
             token_type,
             lexeme,
             literal,
             line,
+            column,
+            span,
+            trivia: Box::new([]),
+        }
+    }
+
+    // the text of the /// comments right before this token, with the slashes
+    // and a space after them taken off, one line per comment
+    pub fn doc(&self) -> Option<String> {
+        let lines: Vec<&str> = self.trivia.iter()
+            .filter(|t| t.kind == TriviaKind::DocComment)
+            .map(|t| t.text.strip_prefix("///").unwrap())
+            .map(|line| line.strip_prefix(' ').unwrap_or(line))
+            .collect();
+
+        if lines.is_empty() {
+            None
+        } else {
+            Some(lines.join("\n"))
         }
     }
 }
@@ -0,0 +1,83 @@
+use crate::{callable::LoxCallable, class::{LoxClass, LoxInstance}, token::Literal};
+
+use std::{cell::RefCell, fmt, rc::Rc};
+
+// everything a Lox expression can evaluate to. heap objects are reference
+// counted, so copying a value around never copies the object behind it
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(Rc<str>),
+    Callable(Rc<dyn LoxCallable>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+}
+
+impl From<&Literal> for Value {
+    fn from(l: &Literal) -> Self {
+        match l {
+            Literal::Nil => Value::Nil,
+            Literal::Bool(b) => Value::Bool(*b),
+            Literal::Number(n) => Value::Number(*n),
+            Literal::String(s) => Value::String(s.as_str().into()),
+        }
+    }
+}
+
+// primitives compare by value, objects by identity
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(l), Value::Bool(r)) => l == r,
+            (Value::Number(l), Value::Number(r)) => l == r,
+            (Value::String(l), Value::String(r)) => l == r,
+            (Value::Callable(l), Value::Callable(r)) => Rc::ptr_eq(l, r),
+            (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+            (Value::Instance(l), Value::Instance(r)) => Rc::ptr_eq(l, r),
+            _ => false,
+        }
+    }
+}
+
+// formats numbers the way jlox does with Java's Double.toString: integral
+// values drop their ".0", and very large or small magnitudes switch to
+// scientific notation like 1.0E7 and 1.0E-4
+fn format_number(n: f64) -> String {
+    if n.is_nan() {
+        return "NaN".to_owned();
+    }
+    if n.is_infinite() {
+        return if n > 0.0 { "Infinity".to_owned() } else { "-Infinity".to_owned() };
+    }
+
+    let magnitude = n.abs();
+    if magnitude != 0.0 && !(1e-3..1e7).contains(&magnitude) {
+        let scientific = format!("{:e}", n);
+        let (mantissa, exponent) = scientific.split_once('e').unwrap();
+
+        if mantissa.contains('.') {
+            format!("{}E{}", mantissa, exponent)
+        } else {
+            format!("{}.0E{}", mantissa, exponent)
+        }
+    } else {
+        format!("{}", n)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", format_number(*n)),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Callable(c) => write!(f, "{:?}", c),
+            Value::Class(c) => write!(f, "{:?}", c),
+            Value::Instance(i) => write!(f, "{:?}", i.borrow()),
+        }
+    }
+}
@@ -0,0 +1,126 @@
+// in lossless mode every byte of the source ends up in a token or its trivia,
+// whether or not the source is valid Lox
+
+use rlox::{scanner::Scanner, token::{Token, TokenType, TriviaKind}};
+use std::{fs, path::Path};
+
+fn scan(source: &str) -> Vec<Token> {
+    let mut scanner = Scanner::new(source);
+    scanner.set_lossless(true);
+    scanner.scan_tokens().value
+}
+
+fn concatenate(tokens: &[Token]) -> String {
+    let mut text = String::new();
+    for t in tokens {
+        for trivia in t.trivia.iter() {
+            text += &trivia.text;
+        }
+        text += &t.lexeme;
+    }
+
+    text
+}
+
+fn assert_round_trips(source: &str) {
+    let tokens = scan(source);
+    assert_eq!(concatenate(&tokens), source);
+
+    // and the pieces are in order, each one starting where the last one ended
+    let mut offset = 0;
+    for t in &tokens {
+        for trivia in t.trivia.iter() {
+            assert_eq!(trivia.span.start, offset, "{:?} in {:?}", trivia, source);
+            offset = trivia.span.end;
+        }
+        assert_eq!(t.span.start, offset, "{} in {:?}", t, source);
+        offset = t.span.end;
+    }
+}
+
+#[test]
+fn trivia_is_attached_to_the_token_after_it() {
+    let tokens = scan("var a; // set up\r\n  /* block */\tprint a;\n");
+    let print = tokens.iter().find(|t| t.token_type == TokenType::Print).unwrap();
+
+    let kinds: Vec<(TriviaKind, &str)> = print.trivia.iter().map(|t| (t.kind, t.text.as_str())).collect();
+    assert_eq!(kinds, vec![
+        (TriviaKind::Whitespace, " "),
+        (TriviaKind::LineComment, "// set up"),
+        (TriviaKind::Newline, "\r\n"),
+        (TriviaKind::Whitespace, "  "),
+        (TriviaKind::BlockComment, "/* block */"),
+        (TriviaKind::Whitespace, "\t"),
+    ]);
+
+    // what's left after the last token goes on the end of file
+    let eof = tokens.last().unwrap();
+    assert_eq!(eof.token_type, TokenType::Eof);
+    assert_eq!(eof.trivia.iter().map(|t| t.kind).collect::<Vec<_>>(), vec![TriviaKind::Newline]);
+}
+
+#[test]
+fn only_doc_comments_are_kept_by_default() {
+    let tokens = Scanner::new("// plain\n/// docs\n  fun f() {}").scan_tokens().value;
+
+    let kinds: Vec<TriviaKind> = tokens[0].trivia.iter().map(|t| t.kind).collect();
+    assert_eq!(kinds, vec![TriviaKind::DocComment]);
+}
+
+#[test]
+fn text_with_errors_is_kept_as_skipped() {
+    let source = "a @ # \"unterminated";
+    let tokens = scan(source);
+
+    let skipped: Vec<&str> = tokens.iter()
+        .flat_map(|t| t.trivia.iter())
+        .filter(|t| t.kind == TriviaKind::Skipped)
+        .map(|t| t.text.as_str())
+        .collect();
+    assert_eq!(skipped, vec!["@", "#", "\"unterminated"]);
+}
+
+#[test]
+fn test_programs_round_trip() {
+    fn visit(dir: &Path) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                visit(&path);
+            } else {
+                assert_round_trips(&fs::read_to_string(&path).unwrap());
+            }
+        }
+    }
+
+    visit(&Path::new(env!("CARGO_MANIFEST_DIR")).join("../test"));
+}
+
+// builds random sources out of pieces that exercise every kind of token and
+// trivia, along with plenty that don't scan, and checks that each round trips
+#[test]
+fn random_sources_round_trip() {
+    const PIECES: &[&str] = &[
+        "var", "fun", "class", "print", "x", "_y1", "café", "変数", "123", "4.5", "6.", ".",
+        "(", ")", "{", "}", ",", ";", "-", "+", "*", "/", "!", "!=", "=", "==", "<", "<=", ">", ">=",
+        " ", "  ", "\t", "\n", "\r\n", "\r", "// comment", "/// doc", "//// four", "/*", "*/", "/* block */",
+        "\"string\"", "\"", "\"\\n\\u{1F980}\"", "\\q", "\\", "r\"raw\"", "r#\"", "\"#", "r",
+        "@", "#", "🦀", "👨‍👩‍👧", "\0",
+    ];
+
+    // xorshift, so failures can be reproduced from the case number
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    for _ in 0..2000 {
+        let length = next() % 40;
+        let source: String = (0..length).map(|_| PIECES[(next() % PIECES.len() as u64) as usize]).collect();
+
+        assert_round_trips(&source);
+    }
+}
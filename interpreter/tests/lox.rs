@@ -0,0 +1,218 @@
+// runs every .lox file under test/ at the top of the repository and checks
+// what it prints against the annotations in its comments, the same way the
+// craftinginterpreters test suite does:
+//
+// print 1; // expect: 1
+// print nil + 1; // expect runtime error: Operands must be two numbers or two strings.
+// var a = ; // Error at ';': Expect expression.
+// // [line 3] Error at end: Expect '}' after block.
+//
+// stdout has to be exactly the expected output, stderr exactly the expected
+// errors, and the exit code 70 after a runtime error and 0 if there were no
+// errors. after compile errors rlox exits with 64, while the bytecode compiler
+// is held to the reference implementations' 65. annotations like
+// "[java line 3]" or "[c line 3]" only apply to the tree-walk interpreter or
+// the bytecode compiler. files containing "// nontest" are skipped.
+//
+// the tree-walk interpreter is tested by default. to run the suite against the
+// bytecode compiler instead, point LOX_INTERPRETER at its binary:
+//
+// LOX_INTERPRETER=../compiler/target/debug/compiler cargo test --test lox
+
+use std::{env, fs, path::{Path, PathBuf}, process::Command};
+
+struct Interpreter {
+    path: PathBuf,
+    args: Vec<String>,
+    // which "[<language> line N]" annotations apply
+    language: &'static str,
+    compile_error_code: i32,
+}
+
+#[derive(Default)]
+struct Expectations {
+    output: Vec<(String, usize)>,
+    errors: Vec<String>,
+    runtime_error: Option<(String, usize)>,
+    exit_code: i32,
+}
+
+#[test]
+fn lox() {
+    let interpreter = match env::var_os("LOX_INTERPRETER") {
+        Some(path) => Interpreter { path: path.into(), args: Vec::new(), language: "c", compile_error_code: 65 },
+        None => Interpreter {
+            path: env!("CARGO_BIN_EXE_rlox").into(),
+            args: vec!["--error-format=short".to_owned()],
+            language: "java",
+            compile_error_code: 64,
+        },
+    };
+
+    let mut files = Vec::new();
+    collect(&Path::new(env!("CARGO_MANIFEST_DIR")).join("../test"), &mut files);
+    files.sort();
+
+    let mut passed = 0;
+    let mut failures = Vec::new();
+    for path in &files {
+        let source = fs::read_to_string(path).unwrap();
+        let expectations = match parse(&source, &interpreter) {
+            Some(e) => e,
+            None => continue,
+        };
+
+        let errors = run(&interpreter, path, &expectations);
+        if errors.is_empty() {
+            passed += 1;
+        } else {
+            failures.push(format!("FAIL {}\n  {}", path.display(), errors.join("\n  ")));
+        }
+    }
+
+    assert!(passed + failures.len() > 0, "no tests found");
+    assert!(failures.is_empty(), "\n{}\n\n{} passed, {} failed", failures.join("\n"), passed, failures.len());
+}
+
+fn collect(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect(&path, files);
+        } else if path.extension().is_some_and(|e| e == "lox") {
+            files.push(path);
+        }
+    }
+}
+
+// returns None for files that aren't tests
+fn parse(source: &str, interpreter: &Interpreter) -> Option<Expectations> {
+    let mut expectations = Expectations::default();
+
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+
+        let comment = match line.find("//") {
+            Some(start) => line[start + 2..].trim_start(),
+            None => continue,
+        };
+
+        if comment.starts_with("nontest") {
+            return None;
+        }
+
+        if let Some(text) = comment.strip_prefix("expect:") {
+            let text = text.strip_prefix(' ').unwrap_or(text);
+            expectations.output.push((text.to_owned(), line_number));
+        } else if let Some(message) = comment.strip_prefix("expect runtime error:") {
+            expectations.runtime_error = Some((message.trim().to_owned(), line_number));
+            expectations.exit_code = 70;
+        } else if comment.starts_with("Error") {
+            expectations.errors.push(format!("[line {}] {}", line_number, comment));
+            expectations.exit_code = interpreter.compile_error_code;
+        } else if let Some((annotation_language, error_line, error)) = error_annotation(comment) {
+            if annotation_language.is_none_or(|l| l == interpreter.language) {
+                expectations.errors.push(format!("[line {}] {}", error_line, error));
+                expectations.exit_code = interpreter.compile_error_code;
+            }
+        }
+    }
+
+    Some(expectations)
+}
+
+// "[line 3] Error ..." or "[java line 3] Error ...", into the language, line and error
+fn error_annotation(comment: &str) -> Option<(Option<&str>, usize, &str)> {
+    let (location, error) = comment.strip_prefix('[')?.split_once("] ")?;
+    if !error.starts_with("Error") {
+        return None;
+    }
+
+    let (language, line) = match location.split_once(' ') {
+        Some((language, rest)) if language != "line" => (Some(language), rest.strip_prefix("line ")?),
+        _ => (None, location.strip_prefix("line ")?),
+    };
+
+    Some((language, line.parse().ok()?, error))
+}
+
+fn run(interpreter: &Interpreter, path: &Path, expectations: &Expectations) -> Vec<String> {
+    let output = Command::new(&interpreter.path).args(&interpreter.args).arg(path).output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    let mut errors = Vec::new();
+
+    let error_lines: Vec<&str> = stderr.lines().collect();
+    match &expectations.runtime_error {
+        Some((message, line)) => check_runtime_error(&error_lines, message, *line, &mut errors),
+        None => check_compile_errors(&error_lines, &expectations.errors, &mut errors),
+    }
+
+    match output.status.code() {
+        Some(code) if code == expectations.exit_code => {},
+        code => errors.push(format!("Expected exit code {} but got {:?}.", expectations.exit_code, code)),
+    }
+
+    let mut output_lines: Vec<&str> = stdout.lines().collect();
+    if output_lines.last() == Some(&"") {
+        output_lines.pop();
+    }
+
+    for (i, line) in output_lines.iter().enumerate() {
+        match expectations.output.get(i) {
+            Some((expected, _)) if expected == line => {},
+            Some((expected, expected_line)) => errors.push(format!("Expected output '{}' on line {} and got '{}'.", expected, expected_line, line)),
+            None => errors.push(format!("Got output '{}' when none was expected.", line)),
+        }
+    }
+    for (expected, expected_line) in expectations.output.iter().skip(output_lines.len()) {
+        errors.push(format!("Missing expected output '{}' on line {}.", expected, expected_line));
+    }
+
+    errors
+}
+
+fn check_runtime_error(error_lines: &[&str], message: &str, line: usize, errors: &mut Vec<String>) {
+    match error_lines.first() {
+        None => {
+            errors.push(format!("Expected runtime error '{}' and got none.", message));
+            return;
+        },
+        Some(actual) if *actual != message => {
+            errors.push(format!("Expected runtime error '{}' and got: {}", message, actual));
+        },
+        Some(_) => {},
+    }
+
+    let trace = format!("[line {}]", line);
+    if !error_lines.iter().skip(1).any(|l| l.starts_with(&trace)) {
+        errors.push(format!("Expected a stack trace ending in '{}' and got: {}", trace, error_lines[1..].join(" / ")));
+    }
+}
+
+fn check_compile_errors(error_lines: &[&str], expected: &[String], errors: &mut Vec<String>) {
+    let mut found = Vec::new();
+
+    for line in error_lines.iter().filter(|l| !l.is_empty()) {
+        // whether it's "[line 3]" or "[c line 3]" is up to the implementation
+        let normalized = match line.strip_prefix('[').and_then(|l| l.split_once("line ")) {
+            Some((_, rest)) => format!("[line {}", rest),
+            None => line.to_string(),
+        };
+
+        if expected.contains(&normalized) {
+            found.push(normalized);
+        } else if normalized.starts_with("[line ") {
+            errors.push(format!("Unexpected error: {}", line));
+        } else {
+            errors.push(format!("Unexpected output on stderr: {}", line));
+        }
+    }
+
+    for e in expected {
+        if !found.contains(e) {
+            errors.push(format!("Missing expected error: {}", e));
+        }
+    }
+}
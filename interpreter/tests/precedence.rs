@@ -0,0 +1,117 @@
+// pins down how expressions group by printing their syntax trees, where every
+// operator is parenthesized along with its operands
+
+use rlox::{parser::{Parser, StmtKind}, scanner::Scanner};
+
+fn parse(source: &str) -> String {
+    let scanned = Scanner::new(format!("{};", source)).scan_tokens();
+    assert!(scanned.diagnostics.is_empty(), "{}: {:?}", source, scanned.diagnostics);
+
+    let parsed = Parser::new(scanned.value).parse();
+    assert!(parsed.diagnostics.is_empty(), "{}: {:?}", source, parsed.diagnostics);
+
+    match &parsed.value[0].kind {
+        StmtKind::Expr(e) => e.to_string(),
+        _ => panic!("{} didn't parse as an expression statement", source),
+    }
+}
+
+fn check(cases: &[(&str, &str)]) {
+    let failures: Vec<String> = cases.iter()
+        .map(|(source, expected)| (source, expected, parse(source)))
+        .filter(|(_, expected, actual)| expected != &actual)
+        .map(|(source, expected, actual)| format!("{}\n  expected {}\n  got      {}", source, expected, actual))
+        .collect();
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn binary_operators_bind_tighter_than_looser_ones() {
+    check(&[
+        ("1 + 2 * 3", "(+ 1 (* 2 3))"),
+        ("1 * 2 + 3", "(+ (* 1 2) 3)"),
+        ("1 - 2 / 3", "(- 1 (/ 2 3))"),
+        ("1 / 2 - 3", "(- (/ 1 2) 3)"),
+        ("1 + 2 < 3 * 4", "(< (+ 1 2) (* 3 4))"),
+        ("1 < 2 == 3 > 4", "(== (< 1 2) (> 3 4))"),
+        ("1 >= 2 != 3 <= 4", "(!= (>= 1 2) (<= 3 4))"),
+        ("1 == 2 and 3 != 4", "(and (== 1 2) (!= 3 4))"),
+        ("1 and 2 or 3 and 4", "(or (and 1 2) (and 3 4))"),
+    ]);
+}
+
+#[test]
+fn binary_operators_are_left_associative() {
+    check(&[
+        ("1 - 2 - 3", "(- (- 1 2) 3)"),
+        ("1 + 2 - 3", "(- (+ 1 2) 3)"),
+        ("1 / 2 / 3", "(/ (/ 1 2) 3)"),
+        ("1 * 2 / 3", "(/ (* 1 2) 3)"),
+        ("1 < 2 < 3", "(< (< 1 2) 3)"),
+        ("1 <= 2 >= 3", "(>= (<= 1 2) 3)"),
+        ("1 == 2 == 3", "(== (== 1 2) 3)"),
+        ("1 != 2 == 3", "(== (!= 1 2) 3)"),
+        ("1 and 2 and 3", "(and (and 1 2) 3)"),
+        ("1 or 2 or 3", "(or (or 1 2) 3)"),
+    ]);
+}
+
+#[test]
+fn unary_operators_bind_tighter_than_binary_ones() {
+    check(&[
+        ("-1 * 2", "(* (- 1) 2)"),
+        ("1 * -2", "(* 1 (- 2))"),
+        ("-1 - -2", "(- (- 1) (- 2))"),
+        ("!true == false", "(== (! true) false)"),
+        ("!!true", "(! (! true))"),
+        ("--1", "(- (- 1))"),
+        ("!a and b", "(and (! a) b)"),
+    ]);
+}
+
+#[test]
+fn calls_and_property_access_bind_tightest() {
+    check(&[
+        ("-f()", "(- (call f))"),
+        ("!a.b", "(! (. a b))"),
+        ("f(1)(2)", "(call (call f 1) 2)"),
+        ("f(1 + 2, 3)", "(call f (+ 1 2) 3)"),
+        ("a.b.c", "(. (. a b) c)"),
+        ("a.b(1).c", "(. (call (. a b) 1) c)"),
+        ("f() + g() * h()", "(+ (call f) (* (call g) (call h)))"),
+        ("super.m(1)", "(call (super m) 1)"),
+        ("this.x", "(. this x)"),
+    ]);
+}
+
+#[test]
+fn grouping_overrides_precedence() {
+    check(&[
+        ("(1 + 2) * 3", "(* (group (+ 1 2)) 3)"),
+        ("1 - (2 - 3)", "(- 1 (group (- 2 3)))"),
+        ("-(1 + 2)", "(- (group (+ 1 2)))"),
+        ("(f)(1)", "(call (group f) 1)"),
+    ]);
+}
+
+#[test]
+fn assignment_binds_loosest_and_is_right_associative() {
+    check(&[
+        ("a = b = c", "(= a (= b c))"),
+        ("a = 1 + 2", "(= a (+ 1 2))"),
+        ("a = b or c", "(= a (or b c))"),
+        ("a.b = c.d = 1", "(= (. a b) (= (. c d) 1))"),
+        ("a.b.c = 1", "(= (. (. a b) c) 1)"),
+    ]);
+}
+
+#[test]
+fn literals() {
+    check(&[
+        ("1.5", "1.5"),
+        ("\"str\"", "\"str\""),
+        ("true", "true"),
+        ("nil", "nil"),
+    ]);
+}
@@ -0,0 +1,202 @@
+// checks the tokens the scanner produces, along with where it says they are
+
+use rlox::{scanner::{IdentifierRule, Scanner}, token::{Literal, Token, TokenType}, Diagnostic};
+
+fn scan(source: &str, rule: IdentifierRule) -> (Vec<Token>, Vec<Diagnostic>) {
+    let mut scanner = Scanner::new(source);
+    scanner.set_identifier_rule(rule);
+
+    let scanned = scanner.scan_tokens();
+    (scanned.value, scanned.diagnostics)
+}
+
+// the type, text, line and column of every token but the last, which is always the end of the file
+fn summarize(tokens: &[Token]) -> Vec<(TokenType, String, u32, u32)> {
+    assert_eq!(tokens.last().unwrap().token_type, TokenType::Eof);
+    tokens[..tokens.len() - 1].iter().map(|t| (t.token_type.clone(), t.lexeme.to_string(), t.line, t.column)).collect()
+}
+
+#[test]
+fn ascii_identifiers_allow_underscores_anywhere() {
+    let (tokens, diagnostics) = scan("_ _a a_1 __init__", IdentifierRule::Ascii);
+
+    assert!(diagnostics.is_empty());
+    assert_eq!(summarize(&tokens), vec![
+        (TokenType::Identifier, "_".to_owned(), 1, 1),
+        (TokenType::Identifier, "_a".to_owned(), 1, 3),
+        (TokenType::Identifier, "a_1".to_owned(), 1, 6),
+        (TokenType::Identifier, "__init__".to_owned(), 1, 10),
+    ]);
+}
+
+#[test]
+fn ascii_identifiers_stop_at_other_letters() {
+    let source = "var café = 変数;";
+    let (tokens, diagnostics) = scan(source, IdentifierRule::Ascii);
+
+    assert_eq!(summarize(&tokens), vec![
+        (TokenType::Var, "var".to_owned(), 1, 1),
+        (TokenType::Identifier, "caf".to_owned(), 1, 5),
+        (TokenType::Equal, "=".to_owned(), 1, 10),
+        (TokenType::SemiColon, ";".to_owned(), 1, 14),
+    ]);
+
+    // each rejected character is reported on its own, spanning all of its bytes
+    let errors: Vec<(&str, &str)> = diagnostics.iter().map(|d| (d.message.as_str(), &source[d.span.start..d.span.end])).collect();
+    assert_eq!(errors, vec![
+        ("Unexpected character.", "é"),
+        ("Unexpected character.", "変"),
+        ("Unexpected character.", "数"),
+    ]);
+}
+
+#[test]
+fn unicode_identifiers_follow_xid() {
+    let (tokens, diagnostics) = scan("var café = 変数 + _ñ1 + Ωmega;", IdentifierRule::Unicode);
+
+    assert!(diagnostics.is_empty());
+    assert_eq!(summarize(&tokens), vec![
+        (TokenType::Var, "var".to_owned(), 1, 1),
+        (TokenType::Identifier, "café".to_owned(), 1, 5),
+        (TokenType::Equal, "=".to_owned(), 1, 10),
+        (TokenType::Identifier, "変数".to_owned(), 1, 12),
+        (TokenType::Plus, "+".to_owned(), 1, 15),
+        (TokenType::Identifier, "_ñ1".to_owned(), 1, 17),
+        (TokenType::Plus, "+".to_owned(), 1, 21),
+        (TokenType::Identifier, "Ωmega".to_owned(), 1, 23),
+        (TokenType::SemiColon, ";".to_owned(), 1, 28),
+    ]);
+}
+
+#[test]
+fn emoji_are_never_identifiers() {
+    for rule in [IdentifierRule::Ascii, IdentifierRule::Unicode] {
+        let source = "a👍b";
+        let (tokens, diagnostics) = scan(source, rule);
+
+        assert_eq!(summarize(&tokens), vec![
+            (TokenType::Identifier, "a".to_owned(), 1, 1),
+            (TokenType::Identifier, "b".to_owned(), 1, 3),
+        ]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(&source[diagnostics[0].span.start..diagnostics[0].span.end], "👍");
+    }
+}
+
+#[test]
+fn strings_hold_any_text() {
+    let source = "\"日本語 👍\" \"a\nü\" x";
+    let (tokens, diagnostics) = scan(source, IdentifierRule::Ascii);
+
+    assert!(diagnostics.is_empty());
+    assert_eq!(tokens[0].literal, Some(Literal::String("日本語 👍".to_owned())));
+    assert_eq!(tokens[1].literal, Some(Literal::String("a\nü".to_owned())));
+
+    // columns count characters, not bytes, and lines carry on after multi-line strings
+    assert_eq!(summarize(&tokens), vec![
+        (TokenType::String, "\"日本語 👍\"".to_owned(), 1, 1),
+        (TokenType::String, "\"a\nü\"".to_owned(), 1, 9),
+        (TokenType::Identifier, "x".to_owned(), 2, 4),
+    ]);
+}
+
+#[test]
+fn comments_hold_any_text() {
+    let (tokens, diagnostics) = scan("// コメント 🦀\nprint 1; // ✨ done", IdentifierRule::Ascii);
+
+    assert!(diagnostics.is_empty());
+    assert_eq!(summarize(&tokens), vec![
+        (TokenType::Print, "print".to_owned(), 2, 1),
+        (TokenType::Number, "1".to_owned(), 2, 7),
+        (TokenType::SemiColon, ";".to_owned(), 2, 8),
+    ]);
+}
+
+#[test]
+fn unterminated_strings_end_at_the_end_of_the_source() {
+    let source = "\"🦀 never closed";
+    let (_, diagnostics) = scan(source, IdentifierRule::Ascii);
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].message, "Unterminated string.");
+    assert_eq!(diagnostics[0].span.end, source.len());
+}
+
+#[test]
+fn escapes_are_decoded() {
+    let (tokens, diagnostics) = scan(r#""\t\n\r\0\\\" \u{41}\u{1F980}""#, IdentifierRule::Ascii);
+
+    assert!(diagnostics.is_empty());
+    assert_eq!(tokens[0].literal, Some(Literal::String("\t\n\r\0\\\" A🦀".to_owned())));
+}
+
+#[test]
+fn bad_escapes_are_reported_where_they_are() {
+    let source = "print \"日本 \\q ok \\u{110000}\";";
+    let (tokens, diagnostics) = scan(source, IdentifierRule::Ascii);
+
+    let errors: Vec<(&str, &str)> = diagnostics.iter().map(|d| (d.message.as_str(), &source[d.span.start..d.span.end])).collect();
+    assert_eq!(errors, vec![
+        ("Unknown escape sequence '\\q'.", "\\q"),
+        ("'\\u{110000}' is not a valid character.", "\\u{110000}"),
+    ]);
+    assert!(diagnostics[0].render(false).contains(" --> 1:11\n"));
+    assert!(diagnostics[1].render(false).contains(" --> 1:17\n"));
+
+    // the string is still scanned, without the bad escapes, so parsing can go on
+    assert_eq!(tokens[1].literal, Some(Literal::String("日本  ok ".to_owned())));
+}
+
+#[test]
+fn raw_strings_are_taken_as_is() {
+    let (tokens, diagnostics) = scan("r\"\\d\\n\" r#\"\"quoted\"\n\"# x", IdentifierRule::Ascii);
+
+    assert!(diagnostics.is_empty());
+    assert_eq!(tokens[0].literal, Some(Literal::String("\\d\\n".to_owned())));
+    assert_eq!(tokens[1].literal, Some(Literal::String("\"quoted\"\n".to_owned())));
+    assert_eq!(summarize(&tokens), vec![
+        (TokenType::String, "r\"\\d\\n\"".to_owned(), 1, 1),
+        (TokenType::String, "r#\"\"quoted\"\n\"#".to_owned(), 1, 9),
+        (TokenType::Identifier, "x".to_owned(), 2, 4),
+    ]);
+}
+
+#[test]
+fn block_comments_nest() {
+    let (tokens, diagnostics) = scan("a /* 1 /* 2 */ 1 */ b /**/ c", IdentifierRule::Ascii);
+
+    assert!(diagnostics.is_empty());
+    assert_eq!(summarize(&tokens), vec![
+        (TokenType::Identifier, "a".to_owned(), 1, 1),
+        (TokenType::Identifier, "b".to_owned(), 1, 21),
+        (TokenType::Identifier, "c".to_owned(), 1, 28),
+    ]);
+}
+
+#[test]
+fn unterminated_block_comments_are_reported_where_they_open() {
+    let source = "a\n  /* outer\n/* inner */\n";
+    let (_, diagnostics) = scan(source, IdentifierRule::Ascii);
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].message, "Unterminated block comment.");
+    assert_eq!(&source[diagnostics[0].span.start..diagnostics[0].span.end], "/*");
+    assert!(diagnostics[0].render(false).contains(" --> 2:3\n"));
+}
+
+#[test]
+fn doc_comments_are_attached_to_the_next_token() {
+    let source = "/// Adds.\n///   Indented.\n//// not docs\n// not docs either\nfun add() {}\n///\nvar x;";
+    let (tokens, diagnostics) = scan(source, IdentifierRule::Ascii);
+
+    assert!(diagnostics.is_empty());
+    assert_eq!(tokens[0].token_type, TokenType::Fun);
+    assert_eq!(tokens[0].doc(), Some("Adds.\n  Indented.".to_owned()));
+    assert_eq!(tokens[0].trivia.len(), 2);
+    assert_eq!(tokens[0].trivia[0].text, "/// Adds.");
+
+    let var = tokens.iter().find(|t| t.token_type == TokenType::Var).unwrap();
+    assert_eq!(var.doc(), Some("".to_owned()));
+
+    assert!(tokens.iter().filter(|t| t.token_type != TokenType::Fun && t.token_type != TokenType::Var).all(|t| t.doc().is_none()));
+}
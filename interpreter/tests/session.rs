@@ -0,0 +1,207 @@
+// drives the interpreter through Session the way a host program would
+
+use rlox::{token::Span, Diagnostic, Error, ErrorFormat, Session, Value};
+use std::{cell::RefCell, io::{self, Write}, rc::Rc};
+
+// an in-memory sink that stays readable after a clone is handed to the session
+#[derive(Clone, Default)]
+struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Buffer {
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// a session writing its output and errors to buffers of its own
+fn captured() -> (Session, Buffer, Buffer) {
+    let (output, errors) = (Buffer::default(), Buffer::default());
+
+    let mut session = Session::new();
+    session.set_output(output.clone());
+    session.set_diagnostic_output(errors.clone(), false);
+
+    (session, output, errors)
+}
+
+// the message and the source text of the one runtime error source runs into
+fn runtime_error(session: &mut Session, source: &str) -> (String, String) {
+    match session.eval(source) {
+        Err(Error::Runtime(d)) => (d.message.clone(), source[d.span.start..d.span.end].to_owned()),
+        result => panic!("expected a runtime error from {:?} and got {:?}", source, result),
+    }
+}
+
+#[test]
+fn natives_are_called_with_their_arguments() {
+    let mut session = Session::new();
+    session.define_native("add", 2, |args| match args {
+        [Value::Number(a), Value::Number(b)] => Ok(Value::Number(a + b)),
+        _ => Err("add takes two numbers.".to_owned()),
+    });
+
+    assert_eq!(session.eval("add(1, 2);").unwrap(), Value::Number(3.0));
+}
+
+#[test]
+fn natives_check_their_arity_before_running() {
+    let calls = Rc::new(RefCell::new(0));
+
+    let mut session = Session::new();
+    let counted = Rc::clone(&calls);
+    session.define_native("one", 1, move |_| {
+        *counted.borrow_mut() += 1;
+        Ok(Value::Nil)
+    });
+
+    assert_eq!(runtime_error(&mut session, "one(1, 2);"), ("Expected 1 arguments but got 2.".to_owned(), ")".to_owned()));
+    assert_eq!(runtime_error(&mut session, "one();"), ("Expected 1 arguments but got 0.".to_owned(), ")".to_owned()));
+    assert_eq!(*calls.borrow(), 0);
+}
+
+#[test]
+fn native_errors_are_runtime_errors_at_the_call() {
+    let mut session = Session::new();
+    session.define_native("fail", 1, |args| Err(format!("Failed with {}.", args[0])));
+
+    let source = "var a = 1;\nprint fail(a);";
+    match session.eval(source) {
+        Err(Error::Runtime(d)) => {
+            assert_eq!(d.message, "Failed with 1.");
+            assert_eq!(d.span.start, source.rfind(')').unwrap());
+            assert_eq!(d.line(), 2);
+        },
+        result => panic!("expected a runtime error and got {:?}", result),
+    }
+}
+
+#[test]
+fn runtime_errors_point_into_the_source_they_happened_in() {
+    let (mut session, _, errors) = captured();
+
+    session.eval("var a = 1;\nfun f() {\n  return nil + 1;\n}").unwrap();
+    match session.eval("f(); // ééééééééééééé") {
+        Err(Error::Runtime(d)) => {
+            assert_eq!(d.line(), 3);
+            assert_eq!(&d.source[d.span.start..d.span.end], "+");
+        },
+        result => panic!("expected a runtime error and got {:?}", result),
+    }
+
+    assert!(errors.contents().contains(" --> 3:14\n  |\n3 |   return nil + 1;\n  |              ^\n"), "{}", errors.contents());
+}
+
+#[test]
+fn diagnostics_never_split_a_character() {
+    let diagnostic = Diagnostic::new("Bad.".to_owned(), Span::new(3, 5), "a\nééé".into());
+
+    assert_eq!(diagnostic.line(), 2);
+    assert!(diagnostic.render(false).contains(" --> 2:1\n  |\n2 | ééé\n  | ^\n"), "{}", diagnostic.render(false));
+}
+
+#[test]
+fn natives_can_hold_on_to_host_state() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+
+    let mut session = Session::new();
+    let sink = Rc::clone(&log);
+    session.define_native("record", 1, move |args| {
+        sink.borrow_mut().push(args[0].to_string());
+        Ok(Value::Number(sink.borrow().len() as f64))
+    });
+
+    let count = session.eval("for (var i = 0; i < 3; i = i + 1) record(i * 2); record(\"done\");").unwrap();
+
+    assert_eq!(count, Value::Number(4.0));
+    assert_eq!(*log.borrow(), vec!["0", "2", "4", "done"]);
+}
+
+#[test]
+fn eval_returns_the_value_of_a_trailing_expression() {
+    let mut session = Session::new();
+
+    assert_eq!(session.eval("1 + 2;").unwrap(), Value::Number(3.0));
+    assert_eq!(session.eval("\"a\" + \"b\";").unwrap(), Value::String("ab".into()));
+    assert_eq!(session.eval("1; var a = 2;").unwrap(), Value::Nil);
+    assert_eq!(session.eval("").unwrap(), Value::Nil);
+}
+
+#[test]
+fn print_output_and_errors_go_to_separate_sinks() {
+    let (mut session, output, errors) = captured();
+
+    session.eval("print \"before\";").unwrap();
+    assert!(session.eval("print \"still\"; print nil + 1; print \"never\";").is_err());
+    assert!(session.eval("print ;").is_err());
+
+    assert_eq!(output.contents(), "before\nstill\n");
+
+    let errors = errors.contents();
+    assert!(errors.contains("error: Operands must be two numbers or two strings.\n"), "{}", errors);
+    assert!(errors.contains("error: Expect expression.\n"), "{}", errors);
+    assert!(!errors.lines().any(|l| l == "before" || l == "still"), "{}", errors);
+}
+
+#[test]
+fn errors_are_rendered_in_the_chosen_format() {
+    let (mut session, _, errors) = captured();
+    session.set_error_format(ErrorFormat::Short);
+
+    assert!(session.eval("var a = 1;\nprint -\"a\";").is_err());
+    assert!(session.eval("print ;").is_err());
+
+    assert_eq!(errors.contents(), "Operand must be a number.\n[line 2]\n[line 1] Error at ';': Expect expression.\n");
+}
+
+#[test]
+fn globals_are_shared_with_the_host() {
+    let (mut session, output, _) = captured();
+
+    session.set_global("limit", Value::Number(10.0));
+    session.eval("var doubled = limit * 2; print limit;").unwrap();
+
+    assert_eq!(output.contents(), "10\n");
+    assert_eq!(session.get_global("doubled"), Some(Value::Number(20.0)));
+    assert_eq!(session.get_global("missing"), None);
+
+    // and they stay defined from one eval to the next
+    session.set_global("limit", Value::Bool(true));
+    assert_eq!(session.eval("limit and doubled;").unwrap(), Value::Number(20.0));
+}
+
+// cargo runs tests on threads with small stacks, like the ones host programs
+// tend to have, so these check that deep recursion is an error and not a crash
+#[test]
+fn deep_recursion_fits_on_a_small_stack() {
+    let mut session = Session::new();
+
+    assert_eq!(session.eval("fun f(n) { if (n > 0) return f(n - 1) + 1; return 0; } f(990);").unwrap(), Value::Number(990.0));
+    assert_eq!(session.eval(&format!("{}1{};", "(".repeat(250), ")".repeat(250))).unwrap(), Value::Number(1.0));
+}
+
+#[test]
+fn runaway_recursion_is_a_runtime_error() {
+    let mut session = Session::new();
+
+    assert_eq!(runtime_error(&mut session, "fun f() { f(); } f();"), ("Stack overflow.".to_owned(), ")".to_owned()));
+}
+
+#[test]
+fn too_much_nesting_is_a_compile_error() {
+    let mut session = Session::new();
+
+    match session.eval(&format!("print {}1{};", "(".repeat(100_000), ")".repeat(100_000))) {
+        Err(Error::Compile(diagnostics)) => assert_eq!(diagnostics[0].message, "Too much nesting."),
+        result => panic!("expected a compile error and got {:?}", result),
+    }
+}